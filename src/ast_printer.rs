@@ -11,7 +11,7 @@ impl AstPrinter for Expr {
                 left,
                 operator,
                 right,
-            } => parenthesize(&operator.lexeme, vec![left, right]),
+            } => parenthesize(operator.lexeme.as_str(), vec![left, right]),
             Expr::Grouping { expression } => parenthesize("group", vec![expression]),
             Expr::Literal { value } => match value {
                 Literal::String(s) => s.clone(),
@@ -19,7 +19,7 @@ impl AstPrinter for Expr {
                 Literal::Nil => "nil".to_string(),
                 Literal::Boolean(b) => b.to_string(),
             },
-            Expr::Unary { operator, right } => parenthesize(&operator.lexeme, vec![right]),
+            Expr::Unary { operator, right } => parenthesize(operator.lexeme.as_str(), vec![right]),
             Expr::Conditional {
                 condition,
                 then,
@@ -54,12 +54,12 @@ mod tests {
     fn test_ast_printer() {
         let expr = Expr::Binary {
             left: Box::new(Expr::Unary {
-                operator: Token::new(TokenType::Minus, "-".to_string(), 1),
+                operator: Token::new(TokenType::Minus, "-", 1),
                 right: Box::new(Expr::Literal {
                     value: Literal::Number(123_f64),
                 }),
             }),
-            operator: Token::new(TokenType::Star, "*".to_string(), 1),
+            operator: Token::new(TokenType::Star, "*", 1),
             right: Box::new(Expr::Grouping {
                 expression: Box::new(Expr::Literal {
                     value: Literal::Number(45.67),
@@ -0,0 +1,368 @@
+use std::collections::HashSet;
+
+use crate::{
+    expr::Expr, natives, stmt::Stmt, symbol::Symbol, token::Literal, token_type::TokenType,
+};
+
+// Replaces expressions whose value is known before running with a literal of it: arithmetic on
+// number literals and calls of pure natives like `sqrt(16)` with literal arguments. Expressions
+// that would fail, like dividing by 0, are left to report their error when they run.
+// Only for whole programs, as a later REPL line could still redefine a native.
+pub fn fold_constants(statements: &mut [Stmt]) {
+    // A native the program declares or assigns a global of the same name for isn't folded
+    let mut redefined = HashSet::new();
+    for statement in statements.iter() {
+        match statement {
+            Stmt::Var { name, .. } | Stmt::Class { name, .. } | Stmt::Enum { name, .. } => {
+                redefined.insert(name.lexeme.symbol());
+            }
+            Stmt::Function { name, .. } => {
+                redefined.insert(name.lexeme.symbol());
+            }
+            Stmt::VarDestructure { names, .. } => {
+                redefined.extend(names.iter().map(|name| name.lexeme.symbol()));
+            }
+            _ => {}
+        }
+    }
+    walk(statements, &mut |expr| {
+        if let Expr::Assign {
+            name, depth: None, ..
+        } = expr
+        {
+            redefined.insert(name.lexeme.symbol());
+        }
+    });
+
+    walk(statements, &mut |expr| {
+        if let Some(value) = constant(expr, &redefined) {
+            *expr = Expr::Literal {
+                value: Literal::Number(value),
+            };
+        }
+    });
+}
+
+// The value of the expression if its operands are number literals already
+fn constant(expr: &Expr, redefined: &HashSet<Symbol>) -> Option<f64> {
+    match expr {
+        Expr::Grouping { expression } => number(expression),
+        Expr::Unary { operator, right } if operator.token_type == TokenType::Minus => {
+            number(right).map(|right| -right)
+        }
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        } => {
+            let (left, right) = (number(left)?, number(right)?);
+            match operator.token_type {
+                TokenType::Plus => Some(left + right),
+                TokenType::Minus => Some(left - right),
+                TokenType::Star => Some(left * right),
+                TokenType::Slash if right != 0.0 => Some(left / right),
+                _ => None,
+            }
+        }
+        Expr::Call {
+            callee, arguments, ..
+        } => {
+            let Expr::Variable { name, depth: None } = callee.as_ref() else {
+                return None;
+            };
+            if redefined.contains(&name.lexeme.symbol()) {
+                return None;
+            }
+
+            let function = natives::pure_function(name.lexeme.as_str())?;
+            match arguments.as_slice() {
+                [argument] => number(argument).map(function),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn number(expr: &Expr) -> Option<f64> {
+    match expr {
+        Expr::Literal {
+            value: Literal::Number(value),
+        } => Some(*value),
+        _ => None,
+    }
+}
+
+// Visits every expression of the statements, the operands before the expression using them
+fn walk(statements: &mut [Stmt], visit: &mut dyn FnMut(&mut Expr)) {
+    for statement in statements {
+        walk_stmt(statement, visit);
+    }
+}
+
+fn walk_stmt(statement: &mut Stmt, visit: &mut dyn FnMut(&mut Expr)) {
+    match statement {
+        Stmt::Block { statements } => walk(statements, visit),
+        Stmt::Class {
+            superclass,
+            mixins,
+            methods,
+            ..
+        } => {
+            if let Some(superclass) = superclass {
+                walk_expr(superclass, visit);
+            }
+            for mixin in mixins {
+                walk_expr(mixin, visit);
+            }
+            walk(methods, visit);
+        }
+        Stmt::Expression { expr } | Stmt::Print { expr } | Stmt::Eprint { expr } => {
+            walk_expr(expr, visit)
+        }
+        Stmt::Function { body, .. } => walk(&mut body.borrow_mut(), visit),
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            walk_expr(condition, visit);
+            walk_stmt(then_branch, visit);
+            if let Some(else_branch) = else_branch {
+                walk_stmt(else_branch, visit);
+            }
+        }
+        Stmt::While {
+            condition,
+            body,
+            increment,
+            ..
+        } => {
+            walk_expr(condition, visit);
+            walk_stmt(body, visit);
+            if let Some(increment) = increment {
+                walk_expr(increment, visit);
+            }
+        }
+        Stmt::ForIn { iterable, body, .. } => {
+            walk_expr(iterable, visit);
+            walk_stmt(body, visit);
+        }
+        Stmt::Assert {
+            condition, message, ..
+        } => {
+            walk_expr(condition, visit);
+            if let Some(message) = message {
+                walk_expr(message, visit);
+            }
+        }
+        Stmt::Return { value, .. } => {
+            if let Some(value) = value {
+                walk_expr(value, visit);
+            }
+        }
+        Stmt::Break { .. } | Stmt::Continue { .. } | Stmt::Enum { .. } => {}
+        Stmt::Switch {
+            value,
+            cases,
+            default,
+            ..
+        } => {
+            walk_expr(value, visit);
+            for (case, body) in cases {
+                walk_expr(case, visit);
+                walk(body, visit);
+            }
+            if let Some(default) = default {
+                walk(default, visit);
+            }
+        }
+        Stmt::Throw { value, .. } => walk_expr(value, visit),
+        Stmt::Try {
+            body,
+            catch,
+            finally,
+        } => {
+            walk(body, visit);
+            if let Some((_, catch)) = catch {
+                walk(catch, visit);
+            }
+            if let Some(finally) = finally {
+                walk(finally, visit);
+            }
+        }
+        Stmt::Var { initializer, .. } => {
+            if let Some(initializer) = initializer {
+                walk_expr(initializer, visit);
+            }
+        }
+        Stmt::Incomplete { statement, .. } => walk_stmt(statement, visit),
+        Stmt::VarDestructure { initializer, .. } => walk_expr(initializer, visit),
+    }
+}
+
+fn walk_expr(expr: &mut Expr, visit: &mut dyn FnMut(&mut Expr)) {
+    match expr {
+        Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+            walk_expr(left, visit);
+            walk_expr(right, visit);
+        }
+        Expr::Grouping { expression } => walk_expr(expression, visit),
+        Expr::Unary { right, .. } => walk_expr(right, visit),
+        Expr::Assign { value, .. } => walk_expr(value, visit),
+        Expr::Conditional {
+            condition,
+            then,
+            r#else,
+        } => {
+            walk_expr(condition, visit);
+            walk_expr(then, visit);
+            walk_expr(r#else, visit);
+        }
+        Expr::Call {
+            callee, arguments, ..
+        } => {
+            walk_expr(callee, visit);
+            for argument in arguments {
+                walk_expr(argument, visit);
+            }
+        }
+        Expr::Cascade {
+            object, arguments, ..
+        } => {
+            walk_expr(object, visit);
+            for argument in arguments {
+                walk_expr(argument, visit);
+            }
+        }
+        Expr::List { elements } => {
+            for element in elements {
+                walk_expr(element, visit);
+            }
+        }
+        Expr::Map { entries } => {
+            for (key, value) in entries {
+                walk_expr(key, visit);
+                walk_expr(value, visit);
+            }
+        }
+        Expr::Function { body, .. } => walk(&mut body.borrow_mut(), visit),
+        Expr::Get { object, .. } | Expr::Delete { object, .. } => walk_expr(object, visit),
+        Expr::Set { object, value, .. } => {
+            walk_expr(object, visit);
+            walk_expr(value, visit);
+        }
+        Expr::Index { object, index, .. } => {
+            walk_expr(object, visit);
+            walk_expr(index, visit);
+        }
+        Expr::IndexSet {
+            object,
+            index,
+            value,
+            ..
+        } => {
+            walk_expr(object, visit);
+            walk_expr(index, visit);
+            walk_expr(value, visit);
+        }
+        Expr::Literal { .. } | Expr::Variable { .. } | Expr::This { .. } | Expr::Super { .. } => {}
+    }
+    visit(expr);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fold_constants;
+    use crate::{
+        expr::Expr, parser::Parser, resolver::Resolvable, resolver::Resolver, scanner::Scanner,
+        stmt::Stmt, token::Literal,
+    };
+
+    fn fold(source: &str) -> Vec<Stmt> {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let mut statements = Parser::new(tokens).parse().expect("Source should parse");
+        statements.resolve(&mut Resolver::new());
+        fold_constants(&mut statements);
+        statements
+    }
+
+    // The expression of the last statement, which has to be a print statement
+    fn printed(statements: &[Stmt]) -> &Expr {
+        match statements.last() {
+            Some(Stmt::Print { expr }) => expr,
+            other => panic!("Expected a print statement, got {:?}", other),
+        }
+    }
+
+    fn folded_number(expr: &Expr) -> Option<f64> {
+        match expr {
+            Expr::Literal {
+                value: Literal::Number(value),
+            } => Some(*value),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_pure_native_calls_with_literal_arguments_are_folded() {
+        for (source, expected) in [
+            ("print sqrt(16);", 4.0),
+            ("print abs(-3);", 3.0),
+            ("print floor(1.5) + ceil((1.2));", 3.0),
+            ("print sqrt(2 * 8) / 2 - 1;", 1.0),
+        ] {
+            let statements = fold(source);
+            assert_eq!(
+                folded_number(printed(&statements)),
+                Some(expected),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_impure_and_redefined_natives_are_not_folded() {
+        for source in [
+            "print clock();",
+            "print random();",
+            "print sqrt(\"16\");",
+            "print sqrt(16, 1);",
+            "print 1 / 0;",
+            "fun sqrt(x) { return x; }\nprint sqrt(16);",
+            "var abs;\nabs = fun (x) => x;\nprint abs(-3);",
+            "fun f() { abs = nil; }\nprint abs(-3);",
+        ] {
+            let statements = fold(source);
+            assert!(
+                folded_number(printed(&statements)).is_none(),
+                "{} was folded",
+                source
+            );
+        }
+
+        // A local of the same name doesn't stop calls of the native elsewhere from being folded
+        let statements = fold("{ var sqrt = 1; }\nprint sqrt(9);");
+        assert_eq!(folded_number(printed(&statements)), Some(3.0));
+    }
+
+    #[test]
+    fn test_folding_reaches_into_functions_and_methods() {
+        let statements = fold("class A { m() { return sqrt(4); } }");
+        let Stmt::Class { methods, .. } = &statements[0] else {
+            panic!("Expected a class");
+        };
+        let Stmt::Function { body, .. } = &methods[0] else {
+            panic!("Expected a method");
+        };
+
+        let body = body.borrow();
+        match &body[0] {
+            Stmt::Return {
+                value: Some(value), ..
+            } => assert_eq!(folded_number(value), Some(2.0)),
+            other => panic!("Expected a return, got {:?}", other),
+        }
+    }
+}
@@ -2,15 +2,22 @@ use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use crate::{
     interpreter::{LoxValue, RuntimeError, RuntimeEvent},
+    symbol::Symbol,
     token::Token,
 };
 
 #[derive(Debug)]
 pub struct Environment {
-    values: HashMap<String, Rc<LoxValue>>,
+    values: HashMap<Symbol, Rc<LoxValue>>,
     enclosing: Option<Rc<RefCell<Environment>>>,
 }
 
+impl Default for Environment {
+    fn default() -> Environment {
+        Environment::new()
+    }
+}
+
 impl Environment {
     pub fn new() -> Environment {
         Environment {
@@ -26,7 +33,26 @@ impl Environment {
         }
     }
 
-    pub fn define(&mut self, name: String, value: Rc<LoxValue>) {
+    // A sibling scope starting out with the same variables and values
+    pub fn copy_scope(&self) -> Environment {
+        Environment {
+            values: self.values.clone(),
+            enclosing: self.enclosing.clone(),
+        }
+    }
+
+    // Names of the variables defined directly in this scope, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.values.keys().map(|name| name.to_string()).collect();
+        names.sort();
+        names
+    }
+
+    pub fn enclosing(&self) -> Option<&Rc<RefCell<Environment>>> {
+        self.enclosing.as_ref()
+    }
+
+    pub fn define(&mut self, name: Symbol, value: Rc<LoxValue>) {
         self.values.insert(name, value);
     }
 
@@ -41,13 +67,28 @@ impl Environment {
         }
     }
 
+    // Like `get_at(None, name)`, but an undefined global reads as nil instead of failing
+    pub fn get_global_or_nil(&self, name: &Token) -> Result<Rc<LoxValue>, RuntimeEvent> {
+        let defined = match self.ancestor(None) {
+            Some(global) => global.borrow().values.contains_key(&name.lexeme.symbol()),
+            None => self.values.contains_key(&name.lexeme.symbol()),
+        };
+
+        match defined {
+            true => self.get_at(None, name),
+            false => Ok(Rc::new(LoxValue::Nil)),
+        }
+    }
+
     fn get_this(&self, name: &Token) -> Result<Rc<LoxValue>, RuntimeEvent> {
-        if self.values.contains_key(&name.lexeme) {
-            return Ok(self
-                .values
-                .get(&name.lexeme)
-                .expect("Value must be present, key was checked")
-                .clone());
+        if let Some(value) = self.values.get(&name.lexeme.symbol()) {
+            if let LoxValue::Uninitialized = value.as_ref() {
+                return Err(RuntimeEvent::Error(RuntimeError {
+                    token: name.clone(),
+                    message: format!("Use of uninitialized variable '{}'.", name.lexeme),
+                }));
+            }
+            return Ok(value.clone());
         }
 
         Err(RuntimeEvent::Error(RuntimeError {
@@ -108,10 +149,10 @@ impl Environment {
     }
 
     fn assign_this(&mut self, name: &Token, value: Rc<LoxValue>) -> Result<(), RuntimeEvent> {
-        if self.values.contains_key(&name.lexeme) {
+        if self.values.contains_key(&name.lexeme.symbol()) {
             *self
                 .values
-                .get_mut(&name.lexeme)
+                .get_mut(&name.lexeme.symbol())
                 .expect("Value must be present, key was checked") = value;
             return Ok(());
         }
@@ -123,10 +164,10 @@ impl Environment {
     }
 
     pub fn assign(&mut self, name: &Token, value: Rc<LoxValue>) -> Result<(), RuntimeEvent> {
-        if self.values.contains_key(&name.lexeme) {
+        if self.values.contains_key(&name.lexeme.symbol()) {
             *self
                 .values
-                .get_mut(&name.lexeme)
+                .get_mut(&name.lexeme.symbol())
                 .expect("Value must be present, key was checked") = value;
             return Ok(());
         }
@@ -141,3 +182,46 @@ impl Environment {
         }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::Environment;
+    use crate::{interpreter::LoxValue, token::Token, token_type::TokenType};
+
+    // Returns the innermost of `depth` blocks nested in a global scope defining `answer`
+    fn nested(depth: usize) -> Rc<RefCell<Environment>> {
+        let mut global = Environment::new();
+        global.define("answer".into(), Rc::new(LoxValue::Number(42.0)));
+
+        let mut current = Rc::new(RefCell::new(global));
+        for _ in 0..depth {
+            let mut block = Environment::new_enclosing(current);
+            // Shadowing locals must not be found by a global lookup
+            block.define("answer".into(), Rc::new(LoxValue::Nil));
+            current = Rc::new(RefCell::new(block));
+        }
+        current
+    }
+
+    fn get_global(environment: &Rc<RefCell<Environment>>) -> Option<f64> {
+        let name = Token::new(TokenType::Identifier, "answer", 1);
+        match environment.borrow().get_at(None, &name).ok()?.as_ref() {
+            LoxValue::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_global_lookup_from_global_scope() {
+        assert_eq!(get_global(&nested(0)), Some(42.0));
+    }
+
+    #[test]
+    fn test_global_lookup_from_nested_scopes() {
+        for depth in 1..=3 {
+            assert_eq!(get_global(&nested(depth)), Some(42.0), "depth {}", depth);
+        }
+    }
+}
@@ -1,45 +1,252 @@
-use crate::{interpreter::RuntimeError, token::Token, token_type::TokenType};
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    io::Write,
+};
 
-static mut HAD_ERROR: bool = false;
-static mut HAD_RUNTIME_ERROR: bool = false;
+use crate::{
+    interpreter::RuntimeError,
+    token::{Span, Token},
+    token_type::TokenType,
+};
 
+// The phase before running a program that reported a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Scan,
+    Parse,
+    Resolve,
+}
+
+#[derive(Clone, Copy, Default)]
+struct ErrorFlags {
+    scan: bool,
+    parse: bool,
+    resolve: bool,
+    runtime: bool,
+}
+
+impl ErrorFlags {
+    fn phase(&mut self, phase: Phase) -> &mut bool {
+        match phase {
+            Phase::Scan => &mut self.scan,
+            Phase::Parse => &mut self.parse,
+            Phase::Resolve => &mut self.resolve,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// A compile time error or warning, as reported by the scanner, parser or resolver
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub phase: Phase,
+    pub line: usize,
+    // Where on the line it happened, e.g. "at 'foo'" or "at end", can be empty
+    pub location: String,
+    pub message: String,
+    // The token it is about, if it was scanned from source
+    pub span: Option<Span>,
+    // The token of parser and resolver diagnostics, the scanner only knows the line
+    pub token: Option<Token>,
+}
+
+impl Diagnostic {
+    // The error as returned by `rlox::run`, or None for warnings
+    pub fn to_error(&self) -> Option<LoxError> {
+        if self.severity == Severity::Warning {
+            return None;
+        }
+
+        let message = self.message.clone();
+        Some(match self.phase {
+            Phase::Scan => LoxError::Scan {
+                line: self.line,
+                message,
+            },
+            Phase::Parse => LoxError::Parse {
+                token: self.token.clone()?,
+                message,
+            },
+            Phase::Resolve => LoxError::Resolve {
+                token: self.token.clone()?,
+                message,
+            },
+        })
+    }
+
+    // The line of the source the diagnostic is about, with the whole token underlined:
+    //   print a >= ;
+    //           ^^
+    pub fn underline(&self, source: &str) -> Option<String> {
+        let span = self.span?;
+        let line = source.lines().nth(span.line.checked_sub(1)?)?;
+        let indent = " ".repeat(span.column.saturating_sub(1));
+        // Tokens without text, like the end of the file, still get a caret
+        Some(format!(
+            "{}\n{}{}",
+            line,
+            indent,
+            "^".repeat(span.length.max(1))
+        ))
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "Error",
+            Severity::Warning => "Warning",
+        };
+        write!(f, "[line {}] {}", self.line, severity)?;
+        if !self.location.is_empty() {
+            write!(f, " {}", self.location)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+// An error of any phase, for library users that show errors themselves
+#[derive(Debug)]
+pub enum LoxError {
+    Scan { line: usize, message: String },
+    Parse { token: Token, message: String },
+    Resolve { token: Token, message: String },
+    Runtime(RuntimeError),
+}
+
+impl From<RuntimeError> for LoxError {
+    fn from(error: RuntimeError) -> Self {
+        LoxError::Runtime(error)
+    }
+}
+
+// The same text as when the error is reported
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoxError::Scan { line, message } => write!(f, "[line {}] Error: {}", line, message),
+            LoxError::Parse { token, message } | LoxError::Resolve { token, message } => write!(
+                f,
+                "[line {}] Error {}: {}",
+                token.line,
+                location(token),
+                message
+            ),
+            LoxError::Runtime(error) => write!(f, "[line {}]: {}", error.token.line, error.message),
+        }
+    }
+}
+
+thread_local! {
+    static FLAGS: Cell<ErrorFlags> = Cell::new(ErrorFlags::default());
+    // Diagnostics are collected here instead of printed while `collect_diagnostics` runs
+    static COLLECTED: RefCell<Option<Vec<Diagnostic>>> = const { RefCell::new(None) };
+}
+
+fn update_flags(update: impl FnOnce(&mut ErrorFlags)) {
+    FLAGS.with(|flags| {
+        let mut updated = flags.get();
+        update(&mut updated);
+        flags.set(updated);
+    });
+}
+
+// Runs `f`, returning the diagnostics it reported instead of printing them.
+// Errors reported this way don't set the error flags.
+pub fn collect_diagnostics<T>(f: impl FnOnce() -> T) -> (T, Vec<Diagnostic>) {
+    let enclosing = COLLECTED.with(|collected| collected.replace(Some(vec![])));
+    let flags = FLAGS.with(Cell::get);
+
+    let result = f();
+
+    FLAGS.with(|current| current.set(flags));
+    let diagnostics = COLLECTED.with(|collected| collected.replace(enclosing));
+    (result, diagnostics.unwrap_or_default())
+}
+
+fn emit(diagnostic: Diagnostic) {
+    COLLECTED.with(|collected| match collected.borrow_mut().as_mut() {
+        Some(diagnostics) => diagnostics.push(diagnostic),
+        None => eprintln!("{}", diagnostic),
+    });
+}
+
+// Reports an error of the scanner, which only knows the line
 pub fn error(line: usize, message: &str) {
-    report(line, "", message);
+    report(Phase::Scan, line, "", message, None);
 }
 
-pub fn runtime_error(error: RuntimeError) {
-    eprintln!("[line {}]: {}", error.token.line, error.message);
-    set_had_runtime_error(true);
+pub fn runtime_error(error: &RuntimeError, err: &mut dyn Write) {
+    writeln!(err, "[line {}]: {}", error.token.line, error.message)
+        .expect("Cannot write to error output");
+    update_flags(|flags| flags.runtime = true);
 }
 
+// Whether any phase before running reported an error
 pub fn had_error() -> bool {
-    unsafe { HAD_ERROR }
+    let flags = FLAGS.with(Cell::get);
+    flags.scan || flags.parse || flags.resolve
 }
 
-pub fn set_had_error(had_error: bool) {
-    unsafe { HAD_ERROR = had_error };
+pub fn had_error_in(phase: Phase) -> bool {
+    *FLAGS.with(Cell::get).phase(phase)
+}
+
+// Forgets all errors reported so far, runtime ones included, e.g. to continue with the next
+// REPL line
+pub fn reset_errors() {
+    update_flags(|flags| *flags = ErrorFlags::default());
 }
 
 pub fn had_runtime_error() -> bool {
-    unsafe { HAD_RUNTIME_ERROR }
+    FLAGS.with(Cell::get).runtime
 }
 
-fn set_had_runtime_error(had_runtime_error: bool) {
-    unsafe { HAD_RUNTIME_ERROR = had_runtime_error };
+pub fn error_token(phase: Phase, token: &Token, message: &str) {
+    report(phase, token.line, &location(token), message, Some(token));
 }
 
-pub fn error_token(token: &Token, message: &str) {
+fn location(token: &Token) -> String {
     match token.token_type {
-        TokenType::Eof => report(token.line, " at end", message),
-        _ => report(
-            token.line,
-            format!("at '{}'", token.lexeme).as_str(),
-            message,
-        ),
+        TokenType::Eof => "at end".to_string(),
+        _ => format!("at '{}'", token.lexeme),
     }
 }
 
-fn report(line: usize, location: &str, message: &str) {
-    eprintln!("[line {}] Error {}: {}", line, location, message);
-    set_had_error(true);
+// Tokens that weren't scanned from source, like desugared ones, have no column
+fn span(token: &Token) -> Option<Span> {
+    (token.column != 0).then(|| token.span())
+}
+
+// Reports a lint of the resolver, which unlike an error doesn't stop the program from running
+pub fn warning_token(token: &Token, message: &str) {
+    emit(Diagnostic {
+        severity: Severity::Warning,
+        phase: Phase::Resolve,
+        line: token.line,
+        location: format!("at '{}'", token.lexeme),
+        message: message.to_string(),
+        span: span(token),
+        token: Some(token.clone()),
+    });
+}
+
+fn report(phase: Phase, line: usize, location: &str, message: &str, token: Option<&Token>) {
+    emit(Diagnostic {
+        severity: Severity::Error,
+        phase,
+        line,
+        location: location.to_string(),
+        message: message.to_string(),
+        span: token.and_then(span),
+        token: token.cloned(),
+    });
+    update_flags(|flags| *flags.phase(phase) = true);
 }
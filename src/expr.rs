@@ -1,4 +1,9 @@
-use crate::token::{Literal, Token};
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    stmt::Stmt,
+    token::{Literal, Token},
+};
 
 #[derive(Debug)]
 pub enum Expr {
@@ -41,6 +46,23 @@ pub enum Expr {
         closing_paren: Token,
         arguments: Vec<Expr>,
     },
+    // `object..method(arguments)` calls the method, but evaluates to the object
+    Cascade {
+        object: Box<Expr>,
+        name: Token,
+        closing_paren: Token,
+        arguments: Vec<Expr>,
+    },
+    List {
+        elements: Vec<Expr>,
+    },
+    Map {
+        entries: Vec<(Expr, Expr)>,
+    },
+    Function {
+        params: Rc<Vec<Token>>,
+        body: Rc<RefCell<Vec<Stmt>>>,
+    },
     Get {
         object: Box<Expr>,
         name: Token,
@@ -49,14 +71,35 @@ pub enum Expr {
         object: Box<Expr>,
         name: Token,
         value: Box<Expr>,
+        // The binary operator of a compound assignment like `+=`, applied to the current value
+        operator: Option<Token>,
+    },
+    // `delete object.name` removes a field, evaluating to whether it existed
+    Delete {
+        object: Box<Expr>,
+        name: Token,
+    },
+    Index {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    },
+    IndexSet {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+        operator: Option<Token>,
     },
     This {
         keyword: Token,
-        depth: usize,
+        depth: Option<usize>,
     },
     Super {
         keyword: Token,
         method: Token,
-        depth: usize,
+        depth: Option<usize>,
+        // Distance to the `this` the looked up method gets bound to
+        this_depth: Option<usize>,
     },
 }
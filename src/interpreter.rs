@@ -2,17 +2,22 @@ use std::{
     cell::RefCell,
     collections::{HashMap, VecDeque},
     fmt::Debug,
+    io::Write,
     rc::Rc,
 };
 
 use crate::{
     environment::Environment,
-    error,
+    error::{self, LoxError, Severity},
     expr::Expr,
     lox_callable::{FunctionStmt, LoxCallable},
     lox_class::LoxClass,
     lox_instance::LoxInstance,
+    natives,
+    profiler::Profiler,
+    resolver::{Resolvable, Resolver},
     stmt::Stmt,
+    symbol::Symbol,
     token::{Literal, Token},
     token_type::TokenType,
 };
@@ -25,8 +30,16 @@ pub enum LoxValue {
     Boolean(bool),
     Callable(LoxCallable),
     Instance(Rc<RefCell<LoxInstance>>),
+    List(Rc<RefCell<Vec<Rc<LoxValue>>>>),
+    Map(Rc<RefCell<MapEntries>>),
+    // Value of a `var x;` in the strict uninitialized mode, reading it is an error
+    Uninitialized,
 }
 
+// Entries in insertion order, keys are unique by equality
+pub type MapEntries = Vec<(Rc<LoxValue>, Rc<LoxValue>)>;
+
+#[derive(Debug)]
 pub struct RuntimeError {
     pub token: Token,
     pub message: String,
@@ -40,12 +53,15 @@ impl RuntimeError {
 
 pub enum RuntimeEvent {
     Error(RuntimeError),
-    Return(Rc<LoxValue>),
+    Return { keyword: Token, value: Rc<LoxValue> },
+    Break { keyword: Token },
+    Continue { keyword: Token },
+    Thrown { keyword: Token, value: Rc<LoxValue> },
 }
 
-impl Literal {
-    fn into(&self) -> LoxValue {
-        match self {
+impl From<&Literal> for LoxValue {
+    fn from(literal: &Literal) -> LoxValue {
+        match literal {
             Literal::Boolean(value) => LoxValue::Boolean(*value),
             Literal::Nil => LoxValue::Nil,
             Literal::Number(value) => LoxValue::Number(*value),
@@ -58,7 +74,49 @@ pub trait Stringifyable {
     fn stringify(&self) -> String;
 }
 
+// Formats a number as the shortest string that parses back to it, whole numbers without ".0".
+// Magnitudes from 1e21 and below 1e-6 use an exponent instead of dozens of zeros.
+pub fn format_number(value: f64) -> String {
+    let magnitude = value.abs();
+    if magnitude.is_finite() && magnitude != 0.0 && !(1e-6..1e21).contains(&magnitude) {
+        format!("{:e}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+// Shorthands for natives and embedders, which pass values around as `Rc<LoxValue>`
 impl LoxValue {
+    pub fn number(value: f64) -> Rc<LoxValue> {
+        Rc::new(LoxValue::Number(value))
+    }
+
+    pub fn string(value: impl Into<String>) -> Rc<LoxValue> {
+        Rc::new(LoxValue::String(value.into()))
+    }
+
+    pub fn boolean(value: bool) -> Rc<LoxValue> {
+        Rc::new(LoxValue::Boolean(value))
+    }
+
+    pub fn nil() -> Rc<LoxValue> {
+        Rc::new(LoxValue::Nil)
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            LoxValue::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_string(&self) -> Option<&str> {
+        match self {
+            LoxValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
     fn is_truthy(&self) -> bool {
         match self {
             LoxValue::Boolean(val) => *val,
@@ -70,403 +128,1247 @@ impl LoxValue {
 
 trait MyPartialEq {
     fn eq(&self, other: &Self) -> bool;
-    fn ne(&self, other: &Self) -> bool;
 }
 
 impl MyPartialEq for Rc<LoxValue> {
     fn eq(&self, other: &Self) -> bool {
-        match (self.as_ref(), other.as_ref()) {
-            (LoxValue::String(l0), LoxValue::String(r0)) => l0 == r0,
-            (LoxValue::Number(l0), LoxValue::Number(r0)) => l0 == r0,
-            (LoxValue::Boolean(l0), LoxValue::Boolean(r0)) => l0 == r0,
-            (LoxValue::Nil, LoxValue::Nil) => true,
-            // For other values, compare by reference
-            _ => Rc::ptr_eq(self, other),
+        values_equal(self, other, &mut vec![])
+    }
+}
+
+// Containers currently being compared further up, as pairs of their addresses
+type Comparing = Vec<(*const (), *const ())>;
+
+// Lists and maps are compared by their contents. A pair of containers that is already being
+// compared further up counts as equal, so comparing cyclic structures terminates. Instances in
+// them are compared by reference, their `equals` method would have to run user code for every
+// map lookup.
+fn values_equal(left: &Rc<LoxValue>, right: &Rc<LoxValue>, comparing: &mut Comparing) -> bool {
+    match (left.as_ref(), right.as_ref()) {
+        (LoxValue::String(l0), LoxValue::String(r0)) => l0 == r0,
+        (LoxValue::Number(l0), LoxValue::Number(r0)) => l0 == r0,
+        (LoxValue::Boolean(l0), LoxValue::Boolean(r0)) => l0 == r0,
+        (LoxValue::Nil, LoxValue::Nil) => true,
+        (LoxValue::Instance(l0), LoxValue::Instance(r0)) => Rc::ptr_eq(l0, r0),
+        // A class can be wrapped in several values, e.g. the result of `classOf()`
+        (
+            LoxValue::Callable(LoxCallable::Class { class: l0 }),
+            LoxValue::Callable(LoxCallable::Class { class: r0 }),
+        ) => Rc::ptr_eq(l0, r0),
+        (LoxValue::List(l0), LoxValue::List(r0)) => {
+            containers_equal(l0, r0, comparing, |left, right, comparing| {
+                left.len() == right.len()
+                    && left
+                        .iter()
+                        .zip(right.iter())
+                        .all(|(left, right)| values_equal(left, right, comparing))
+            })
+        }
+        (LoxValue::Map(l0), LoxValue::Map(r0)) => {
+            containers_equal(l0, r0, comparing, |left, right, comparing| {
+                left.len() == right.len()
+                    && left.iter().all(|(key, value)| {
+                        right.iter().any(|(other_key, other_value)| {
+                            values_equal(key, other_key, comparing)
+                                && values_equal(value, other_value, comparing)
+                        })
+                    })
+            })
         }
+        // For other values, compare by reference
+        _ => Rc::ptr_eq(left, right),
     }
+}
 
-    fn ne(&self, other: &Self) -> bool {
-        !self.eq(other)
+fn containers_equal<T>(
+    left: &Rc<RefCell<T>>,
+    right: &Rc<RefCell<T>>,
+    comparing: &mut Comparing,
+    contents_equal: impl FnOnce(&T, &T, &mut Comparing) -> bool,
+) -> bool {
+    let pair = (
+        Rc::as_ptr(left) as *const (),
+        Rc::as_ptr(right) as *const (),
+    );
+    if Rc::ptr_eq(left, right) || comparing.contains(&pair) {
+        return true;
     }
+
+    comparing.push(pair);
+    let equal = contents_equal(&left.borrow(), &right.borrow(), comparing);
+    comparing.pop();
+    equal
 }
 
 impl Stringifyable for LoxValue {
     fn stringify(&self) -> String {
-        match self {
-            LoxValue::Nil => String::from("nil"),
-            LoxValue::Boolean(value) => value.to_string(),
-            LoxValue::Number(value) => value.to_string(),
-            LoxValue::String(value) => value.clone(),
-            LoxValue::Callable(value) => value.stringify(),
-            LoxValue::Instance(value) => value.borrow().stringify(),
-        }
+        stringify_value(self, &mut vec![])
     }
 }
 
-pub struct Interpreter {
-    environment: Rc<RefCell<Environment>>,
-}
-
-impl Interpreter {
-    pub fn new() -> Interpreter {
-        let global_env = Rc::new(RefCell::new(Environment::new()));
-
-        global_env.borrow_mut().define(
-            "clock".to_string(),
-            Rc::new(LoxValue::Callable(LoxCallable::ClockFunction)),
-        );
-
-        Interpreter {
-            environment: global_env,
+// Containers currently being printed further up, as their addresses. These print as `[...]`
+// when they are reached again, so printing cyclic structures terminates.
+fn stringify_value(value: &LoxValue, printing: &mut Vec<*const ()>) -> String {
+    match value {
+        LoxValue::Nil => String::from("nil"),
+        LoxValue::Boolean(value) => value.to_string(),
+        LoxValue::Number(value) => format_number(*value),
+        LoxValue::String(value) => value.clone(),
+        LoxValue::Callable(value) => value.stringify(),
+        LoxValue::Instance(value) => value.borrow().stringify(),
+        LoxValue::List(elements) => {
+            stringify_container(elements, printing, |elements, printing| {
+                let elements: Vec<String> = elements
+                    .iter()
+                    .map(|element| stringify_value(element, printing))
+                    .collect();
+                format!("[{}]", elements.join(", "))
+            })
         }
-    }
-
-    pub fn interpret(&mut self, statements: Vec<Stmt>) {
-        for statement in statements {
-            if let Err(err) = statement.evaluate(self.environment.clone()) {
-                match err {
-                    RuntimeEvent::Error(err) => {
-                        error::runtime_error(err);
-                        return;
-                    }
-                    _ => panic!("Unhandled return statement"),
-                }
+        LoxValue::Map(entries) => stringify_container(entries, printing, |entries, printing| {
+            let entries: Vec<String> = entries
+                .iter()
+                .map(|(key, value)| {
+                    let key = stringify_value(key, printing);
+                    format!("{}: {}", key, stringify_value(value, printing))
+                })
+                .collect();
+            match entries.is_empty() {
+                true => "[:]".to_string(),
+                false => format!("[{}]", entries.join(", ")),
             }
-        }
+        }),
+        // Never read by a program, see `Environment::get_at`
+        LoxValue::Uninitialized => String::from("<uninitialized>"),
     }
 }
 
-pub trait Evaluatable<T> {
-    fn evaluate(&self, environment: Rc<RefCell<Environment>>) -> Result<T, RuntimeEvent>;
+fn stringify_container<T>(
+    container: &Rc<RefCell<T>>,
+    printing: &mut Vec<*const ()>,
+    stringify_contents: impl FnOnce(&T, &mut Vec<*const ()>) -> String,
+) -> String {
+    let address = Rc::as_ptr(container) as *const ();
+    if printing.contains(&address) {
+        return "[...]".to_string();
+    }
+
+    printing.push(address);
+    let string = stringify_contents(&container.borrow(), printing);
+    printing.pop();
+    string
 }
 
-impl Evaluatable<()> for Stmt {
-    fn evaluate(&self, environment: Rc<RefCell<Environment>>) -> Result<(), RuntimeEvent> {
-        match self {
-            Stmt::Expression { expr } => {
-                expr.evaluate(environment)?;
-                Ok(())
-            }
-            Stmt::Print { expr } => {
-                let value = expr.evaluate(environment)?;
-                println!("{}", value.stringify());
-                Ok(())
-            }
-            Stmt::Var { name, initializer } => {
-                let mut value = Rc::new(LoxValue::Nil);
+// Opt-in language modes, the default configuration behaves like reference Lox
+#[derive(Default)]
+pub struct InterpreterConfig {
+    // Let numeric strings take part in arithmetic and comparisons (see `try_coerce_number`)
+    pub loose_coercion: bool,
+    // Make `sleep()` return immediately, for fast test runs
+    pub instant_sleep: bool,
+    // Make `and`/`or` evaluate to a boolean instead of the deciding operand
+    pub strict_booleans: bool,
+    // Record the time spent per function, see `Interpreter::profile_report`
+    pub profile: bool,
+    // Scan `print` as a name rather than a keyword, so it calls the native `print()` function
+    // and can be redefined. Only read when setting up the scanner.
+    pub print_function: bool,
+    // Make a function body ending with an expression return its value instead of nil, see
+    // `Parser::with_implicit_returns`. Only read when setting up the parser.
+    pub implicit_returns: bool,
+    // Let the last expression of a source leave out its `;`, for `--print-last`, see
+    // `Parser::with_optional_last_semicolon`. Only read when setting up the parser.
+    pub optional_last_semicolon: bool,
+    // Make reading a variable declared without initializer an error until it is assigned
+    pub strict_uninitialized: bool,
+    // Let `this` outside of classes refer to a global object, whose fields act like globals.
+    // The resolver allows it as well, see `Resolver::for_config`.
+    pub global_this: bool,
+    // Let reading an undefined global give nil, e.g. for optional settings of embedded scripts.
+    // Assigning to one is still an error.
+    pub nil_for_undefined_globals: bool,
+    // Let `init` return another instance, which calling the class then gives instead of `this`.
+    // The resolver allows it as well, see `Resolver::for_config`.
+    pub factory_initializers: bool,
+    // Define natives that read files, like `readFile()`. Off for running untrusted code.
+    pub filesystem: bool,
+    // Define `exit()`, which ends the whole process
+    pub process_exit: bool,
+}
 
-                if let Some(expr) = initializer {
-                    value = expr.evaluate(environment.clone())?;
-                }
+// Coercion rules of the loose mode: numbers are used as they are and a string counts as a number
+// if, ignoring surrounding whitespace, it is an optionally negative decimal like `3`, `-10` or `2.5`.
+// Everything else is not numeric, including empty strings, `1e3`, `0x10`, `inf`, `.5` and `5.`.
+// Equality is never coerced, `"3" == 3` stays false.
+fn try_coerce_number(value: &LoxValue) -> Option<f64> {
+    match value {
+        LoxValue::Number(num) => Some(*num),
+        LoxValue::String(string) => {
+            let trimmed = string.trim();
+            let unsigned = trimmed.strip_prefix('-').unwrap_or(trimmed);
+            let (integer, fraction) = match unsigned.split_once('.') {
+                Some((integer, fraction)) => (integer, Some(fraction)),
+                None => (unsigned, None),
+            };
 
-                environment.borrow_mut().define(name.lexeme.clone(), value);
-                Ok(())
-            }
-            Stmt::Block { statements } => {
-                statements.evaluate(Rc::new(RefCell::new(Environment::new_enclosing(
-                    environment,
-                ))))?;
-                Ok(())
-            }
-            Stmt::If {
-                condition,
-                then_branch,
-                else_branch,
-            } => {
-                if condition.evaluate(environment.clone())?.is_truthy() {
-                    then_branch.evaluate(environment)?;
-                } else if let Some(else_statement) = else_branch {
-                    else_statement.evaluate(environment)?;
-                }
+            let is_digits =
+                |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
 
-                Ok(())
+            if is_digits(integer) && fraction.is_none_or(is_digits) {
+                trimmed.parse().ok()
+            } else {
+                None
             }
-            Stmt::While { condition, body } => {
-                while condition.evaluate(environment.clone())?.is_truthy() {
-                    body.evaluate(environment.clone())?;
-                }
+        }
+        _ => None,
+    }
+}
 
-                Ok(())
-            }
-            Stmt::Function { name, params, body } => {
-                let function = LoxValue::Callable(LoxCallable::new_function(
-                    Rc::new(FunctionStmt {
-                        name: name.clone(),
-                        params: params.clone(),
-                        body: body.clone(),
-                    }),
-                    environment.clone(),
-                    false,
-                ));
+pub struct Interpreter {
+    config: InterpreterConfig,
+    environment: Rc<RefCell<Environment>>,
+    // Source of the `clock()` native in seconds, replaceable to make scripts deterministic
+    clock: Box<dyn Fn() -> f64>,
+    // Source of `random()` and `randInt()`, seeded from the time unless `seed_random` is called
+    rng: RefCell<natives::Rng>,
+    // Sinks for `print` and `eprint`
+    out: RefCell<Box<dyn Write>>,
+    err: RefCell<Box<dyn Write>>,
+    profiler: Option<RefCell<Profiler>>,
+}
 
-                environment
-                    .borrow_mut()
-                    .define(name.lexeme.to_owned(), Rc::new(function));
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Interpreter::new_with_config(InterpreterConfig::default())
+    }
+}
 
-                Ok(())
-            }
-            Stmt::Return { keyword: _, value } => {
-                let value = match value {
-                    Some(v) => v.evaluate(environment)?,
-                    None => Rc::new(LoxValue::Nil),
-                };
+impl Interpreter {
+    pub fn new_with_config(config: InterpreterConfig) -> Interpreter {
+        Interpreter::with_writers(
+            config,
+            Box::new(std::io::stdout()),
+            Box::new(std::io::stderr()),
+        )
+    }
 
-                Err(RuntimeEvent::Return(value))
-            }
-            Stmt::Class {
-                name,
-                superclass,
-                methods,
-            } => {
-                let superclass = if let Some(superclass_expr) = superclass {
-                    let superclass_value = superclass_expr.evaluate(environment.clone())?;
+    pub fn with_writers(
+        config: InterpreterConfig,
+        out: Box<dyn Write>,
+        err: Box<dyn Write>,
+    ) -> Interpreter {
+        Interpreter {
+            profiler: config.profile.then(RefCell::default),
+            environment: Interpreter::global_environment(&config),
+            config,
+            clock: Box::new(|| {
+                let now = std::time::SystemTime::now();
+                let duration = now
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .expect("Time went backwards");
+                duration.as_secs_f64()
+            }),
+            rng: RefCell::new(natives::Rng::from_time()),
+            out: RefCell::new(out),
+            err: RefCell::new(err),
+        }
+    }
 
-                    if !matches!(
-                        superclass_value.as_ref(),
-                        LoxValue::Callable(LoxCallable::Class { .. })
-                    ) {
-                        let error_token = match superclass_expr {
-                            Expr::Variable { name, .. } => name.clone(),
-                            _ => unreachable!(),
-                        };
+    // Fresh global scope holding only the natives, and the global `this` if enabled
+    fn global_environment(config: &InterpreterConfig) -> Rc<RefCell<Environment>> {
+        let global_env = Rc::new(RefCell::new(Environment::new()));
+        natives::define_natives(&mut global_env.borrow_mut(), config);
+        if config.global_this {
+            let class = Rc::new(LoxClass::new(Symbol::intern("Global"), None, vec![]));
+            let instance = LoxValue::Instance(Rc::new(RefCell::new(LoxInstance::new(class))));
+            global_env
+                .borrow_mut()
+                .define(Symbol::THIS, Rc::new(instance));
+        }
+        global_env
+    }
 
-                        return Err(RuntimeEvent::Error(RuntimeError::new(
-                            error_token,
-                            "Superclass must be a class.".to_string(),
-                        )));
-                    }
+    // Discard all user-defined globals, keeping the configuration, clock and writers
+    pub fn reset(&mut self) {
+        self.environment = Interpreter::global_environment(&self.config);
+    }
 
-                    Some(superclass_value)
-                } else {
-                    None
-                };
+    pub fn set_clock(&mut self, clock: impl Fn() -> f64 + 'static) {
+        self.clock = Box::new(clock);
+    }
 
-                {
-                    // Bind the own classname (in the beginning this is Nil, as class is not fully initialized)
-                    let mut env_mut = environment.borrow_mut();
-                    env_mut.define(name.lexeme.to_string(), Rc::new(LoxValue::Nil));
-                }
+    // Makes the following random numbers the same on every run with the same seed
+    pub fn seed_random(&self, seed: u64) {
+        *self.rng.borrow_mut() = natives::Rng::new(seed);
+    }
 
-                let mut parent_environment = None;
+    pub(crate) fn random(&self) -> f64 {
+        self.rng.borrow_mut().next_f64()
+    }
 
-                let mut environment = if let Some(superclass) = &superclass {
-                    parent_environment = Some(environment.clone());
-                    let super_env = Rc::new(RefCell::new(Environment::new_enclosing(environment)));
-                    super_env
-                        .borrow_mut()
-                        .define("super".to_string(), superclass.clone());
-                    super_env
-                } else {
-                    environment
-                };
+    pub fn config(&self) -> &InterpreterConfig {
+        &self.config
+    }
 
-                let method_map: HashMap<String, Rc<LoxValue>> = methods
-                    .iter()
-                    .map(|method| match method {
-                        Stmt::Function { name, params, body } => (
-                            name.lexeme.to_string(),
-                            Rc::new(LoxValue::Callable(LoxCallable::new_function(
-                                Rc::new(FunctionStmt {
-                                    name: name.clone(),
-                                    params: params.clone(),
-                                    body: body.clone(),
-                                }),
-                                // This is either the current environment, or the one which is bound with "super"
-                                environment.clone(),
-                                name.lexeme == "init",
-                            ))),
-                        ),
-                        _ => panic!("Class can only contain methods."),
-                    })
-                    .collect();
+    // Used by the print statement as well as the native of the same name
+    pub(crate) fn print(&self, value: &LoxValue) {
+        let mut out = self.out.borrow_mut();
+        writeln!(out, "{}", value.stringify()).expect("Cannot write to output");
+        // Flushed right away, so the output stays in order with errors on the other stream
+        out.flush().expect("Cannot flush output");
+    }
 
-                let class = LoxValue::Callable(LoxCallable::Class {
-                    class: Rc::new(LoxClass::new(
-                        name.lexeme.to_string(),
-                        superclass,
-                        method_map,
-                    )),
-                });
+    pub fn profiler(&self) -> Option<&RefCell<Profiler>> {
+        self.profiler.as_ref()
+    }
 
-                if let Some(old_environment) = parent_environment {
-                    environment = old_environment;
-                }
+    // Summary of the calls made so far, if profiling is enabled
+    pub fn profile_report(&self) -> Option<String> {
+        Some(self.profiler.as_ref()?.borrow().report())
+    }
 
-                environment.borrow_mut().assign(name, Rc::new(class))?;
+    pub fn now(&self) -> f64 {
+        (self.clock)()
+    }
 
-                Ok(())
+    // Instances can define their own equality with an `equals(other)` method. It only applies to
+    // the instance itself, within lists and maps it is compared like a map key, see `values_equal`.
+    fn equals(
+        &self,
+        left: &Rc<LoxValue>,
+        right: &Rc<LoxValue>,
+        operator: &Token,
+    ) -> Result<bool, RuntimeEvent> {
+        if let LoxValue::Instance(instance) = left.as_ref() {
+            let equals = LoxInstance::bound_method(instance, Symbol::EQUALS);
+            if let Some(LoxValue::Callable(equals)) = equals.as_deref() {
+                let result = equals.call(self, VecDeque::from([right.clone()]), operator)?;
+                return Ok(result.is_truthy());
             }
         }
+
+        Ok(left.eq(right))
     }
-}
 
-impl Evaluatable<()> for Vec<Stmt> {
-    fn evaluate(&self, environment: Rc<RefCell<Environment>>) -> Result<(), RuntimeEvent> {
-        for statement in self {
-            statement.evaluate(environment.clone())?;
+    fn call(
+        &self,
+        callee: &LoxValue,
+        arguments: VecDeque<Rc<LoxValue>>,
+        closing_paren: &Token,
+    ) -> Result<Rc<LoxValue>, RuntimeEvent> {
+        match callee {
+            LoxValue::Callable(callable) => callable.call(self, arguments, closing_paren),
+            _ => Err(RuntimeEvent::Error(RuntimeError::new(
+                closing_paren.to_owned(),
+                "Can only call functions and classes.".to_string(),
+            ))),
         }
-
-        Ok(())
     }
-}
 
-impl Evaluatable<Rc<LoxValue>> for Expr {
-    fn evaluate(
+    // Applies a binary operator, shared by binary expressions and compound assignments
+    fn binary(
         &self,
-        environment: Rc<RefCell<Environment>>,
+        left: &Rc<LoxValue>,
+        operator: &Token,
+        right: &Rc<LoxValue>,
     ) -> Result<Rc<LoxValue>, RuntimeEvent> {
-        match self {
-            Expr::Literal { value } => Ok(Rc::new(value.into())),
-            Expr::Grouping { expression } => expression.evaluate(environment),
-            Expr::Unary { operator, right } => {
-                let right = right.evaluate(environment)?;
-                let right = right.as_ref();
+        let left_value = left.as_ref();
+        let right_value = right.as_ref();
 
-                match operator.token_type {
-                    TokenType::Minus => match right {
-                        LoxValue::Number(num) => Ok(Rc::new(LoxValue::Number(-num))),
-                        _ => Err(RuntimeEvent::Error(RuntimeError::new(
+        match operator.token_type {
+            // Arithmetic operations
+            TokenType::Minus => match self.number_operands(left_value, right_value) {
+                Some((left_num, right_num)) => Ok(Rc::new(LoxValue::Number(left_num - right_num))),
+                None => Err(RuntimeEvent::Error(RuntimeError::new(
+                    operator.to_owned(),
+                    "Operands must be numbers.".to_string(),
+                ))),
+            },
+            TokenType::Slash => match self.number_operands(left_value, right_value) {
+                Some((left_num, right_num)) => {
+                    if right_num == 0_f64 {
+                        return Err(RuntimeEvent::Error(RuntimeError::new(
                             operator.to_owned(),
-                            "Cannot negate non numeric value".to_string(),
-                        ))),
-                    },
-                    TokenType::Bang => Ok(Rc::new(LoxValue::Boolean(!right.is_truthy()))),
-                    _ => panic!("Invalid unary expression in AST"),
+                            "Cannot divide by 0.".to_string(),
+                        )));
+                    }
+                    Ok(Rc::new(LoxValue::Number(left_num / right_num)))
                 }
-            }
-            Expr::Binary {
-                left,
-                operator,
-                right,
-            } => {
-                let left = left.evaluate(environment.clone())?;
-                let left_value = left.as_ref();
+                None => Err(RuntimeEvent::Error(RuntimeError::new(
+                    operator.to_owned(),
+                    "Operands must be numbers.".to_string(),
+                ))),
+            },
+            TokenType::Star => match self.number_operands(left_value, right_value) {
+                Some((left_num, right_num)) => Ok(Rc::new(LoxValue::Number(left_num * right_num))),
+                None => Err(RuntimeEvent::Error(RuntimeError::new(
+                    operator.to_owned(),
+                    "Operands must be numbers.".to_string(),
+                ))),
+            },
+            TokenType::Plus => {
+                match (left_value, right_value) {
+                    (LoxValue::Number(left_num), LoxValue::Number(right_num)) => {
+                        Ok(Rc::new(LoxValue::Number(left_num + right_num)))
+                    }
 
-                let right = right.evaluate(environment)?;
-                let right_value = right.as_ref();
+                    // Two strings are always concatenated, even if both are numeric
+                    (LoxValue::String(left_str), LoxValue::String(right_str)) => Ok(Rc::new(
+                        LoxValue::String(format!("{}{}", left_str, right_str)),
+                    )),
 
-                match operator.token_type {
-                    // Arithmetic operations
-                    TokenType::Minus => match (left_value, right_value) {
-                        (LoxValue::Number(left_num), LoxValue::Number(right_num)) => {
-                            Ok(Rc::new(LoxValue::Number(left_num - right_num)))
-                        }
-                        _ => Err(RuntimeEvent::Error(RuntimeError::new(
-                            operator.to_owned(),
-                            "Operands must be numbers.".to_string(),
-                        ))),
-                    },
-                    TokenType::Slash => match (left_value, right_value) {
-                        (LoxValue::Number(left_num), LoxValue::Number(right_num)) => {
-                            if right_num == &0_f64 {
-                                return Err(RuntimeEvent::Error(RuntimeError::new(
-                                    operator.to_owned(),
-                                    "Cannot divide by 0.".to_string(),
-                                )));
-                            }
-                            Ok(Rc::new(LoxValue::Number(left_num / right_num)))
-                        }
-                        _ => Err(RuntimeEvent::Error(RuntimeError::new(
-                            operator.to_owned(),
-                            "Operands must be numbers.".to_string(),
-                        ))),
-                    },
-                    TokenType::Star => match (left_value, right_value) {
-                        (LoxValue::Number(left_num), LoxValue::Number(right_num)) => {
-                            Ok(Rc::new(LoxValue::Number(left_num * right_num)))
+                    _ => match self.number_operands(left_value, right_value) {
+                        // In loose mode, a number and a numeric string are added as numbers
+                        Some((left_num, right_num)) => {
+                            Ok(Rc::new(LoxValue::Number(left_num + right_num)))
                         }
-                        _ => Err(RuntimeEvent::Error(RuntimeError::new(
-                            operator.to_owned(),
-                            "Operands must be numbers.".to_string(),
-                        ))),
-                    },
-                    TokenType::Plus => {
-                        match (left_value, right_value) {
-                            (LoxValue::Number(left_num), LoxValue::Number(right_num)) => {
-                                Ok(Rc::new(LoxValue::Number(left_num + right_num)))
-                            }
 
-                            // If either one of the values is a str, we cast the other one to a string
+                        // If either one of the values is a str, we cast the other one to a string
+                        None => match (left_value, right_value) {
                             (LoxValue::String(left_str), right_val) => Ok(Rc::new(
                                 LoxValue::String(format!("{}{}", left_str, right_val.stringify())),
                             )),
                             (left_val, LoxValue::String(right_str)) => Ok(Rc::new(
                                 LoxValue::String(format!("{}{}", left_val.stringify(), right_str)),
                             )),
-
                             _ => Err(RuntimeEvent::Error(RuntimeError::new(
                                 operator.to_owned(),
                                 "Operands must be two numbers or two strings.".to_string(),
                             ))),
-                        }
-                    }
-
-                    // Comparison operations
-                    TokenType::Greater => match (left_value, right_value) {
-                        (LoxValue::Number(left_num), LoxValue::Number(right_num)) => {
-                            Ok(Rc::new(LoxValue::Boolean(left_num > right_num)))
-                        }
-                        _ => Err(RuntimeEvent::Error(RuntimeError::new(
-                            operator.to_owned(),
-                            "Operands must be numbers.".to_string(),
-                        ))),
-                    },
-                    TokenType::GreaterEqual => match (left_value, right_value) {
-                        (LoxValue::Number(left_num), LoxValue::Number(right_num)) => {
-                            Ok(Rc::new(LoxValue::Boolean(left_num >= right_num)))
-                        }
-                        _ => Err(RuntimeEvent::Error(RuntimeError::new(
-                            operator.to_owned(),
-                            "Operands must be numbers.".to_string(),
-                        ))),
-                    },
-                    TokenType::Less => match (left_value, right_value) {
-                        (LoxValue::Number(left_num), LoxValue::Number(right_num)) => {
-                            Ok(Rc::new(LoxValue::Boolean(left_num < right_num)))
-                        }
-                        _ => Err(RuntimeEvent::Error(RuntimeError::new(
-                            operator.to_owned(),
-                            "Operands must be numbers.".to_string(),
-                        ))),
-                    },
-                    TokenType::LessEqual => match (left_value, right_value) {
-                        (LoxValue::Number(left_num), LoxValue::Number(right_num)) => {
-                            Ok(Rc::new(LoxValue::Boolean(left_num <= right_num)))
-                        }
-                        _ => Err(RuntimeEvent::Error(RuntimeError::new(
-                            operator.to_owned(),
-                            "Operands must be numbers.".to_string(),
-                        ))),
+                        },
                     },
+                }
+            }
 
-                    // Equality operations
-                    TokenType::BangEqual => Ok(Rc::new(LoxValue::Boolean(left.ne(&right)))),
-                    TokenType::EqualEqual => Ok(Rc::new(LoxValue::Boolean(left.eq(&right)))),
-                    _ => Err(RuntimeEvent::Error(RuntimeError::new(
+            // Comparison operations
+            TokenType::Greater => match self.number_operands(left_value, right_value) {
+                Some((left_num, right_num)) => Ok(Rc::new(LoxValue::Boolean(left_num > right_num))),
+                None => Err(comparison_error(operator, left_value, right_value)),
+            },
+            TokenType::GreaterEqual => match self.number_operands(left_value, right_value) {
+                Some((left_num, right_num)) => {
+                    Ok(Rc::new(LoxValue::Boolean(left_num >= right_num)))
+                }
+                None => Err(comparison_error(operator, left_value, right_value)),
+            },
+            TokenType::Less => match self.number_operands(left_value, right_value) {
+                Some((left_num, right_num)) => Ok(Rc::new(LoxValue::Boolean(left_num < right_num))),
+                None => Err(comparison_error(operator, left_value, right_value)),
+            },
+            TokenType::LessEqual => match self.number_operands(left_value, right_value) {
+                Some((left_num, right_num)) => {
+                    Ok(Rc::new(LoxValue::Boolean(left_num <= right_num)))
+                }
+                None => Err(comparison_error(operator, left_value, right_value)),
+            },
+
+            TokenType::LessLess | TokenType::GreaterGreater | TokenType::GreaterGreaterGreater => {
+                match self.number_operands(left_value, right_value) {
+                    Some((left_num, right_num)) => shift(operator, left_num, right_num),
+                    None => Err(RuntimeEvent::Error(RuntimeError::new(
                         operator.to_owned(),
-                        "Invalid binary operator.".to_string(),
+                        "Operands must be numbers.".to_string(),
                     ))),
                 }
             }
-            Expr::Variable { name, depth } => environment.borrow().get_at(*depth, name),
-            Expr::Assign { name, value, depth } => {
-                let value = value.evaluate(environment.clone())?;
-                environment
-                    .borrow_mut()
-                    .assign_at(*depth, name, value.clone())?;
-                Ok(value)
-            }
+
+            // Only instances of the class or its subclasses are, any other value isn't
+            TokenType::Is => match right_value {
+                LoxValue::Callable(LoxCallable::Class { class }) => {
+                    let is_instance = match left_value {
+                        LoxValue::Instance(instance) => instance.borrow().is_instance_of(class),
+                        _ => false,
+                    };
+                    Ok(Rc::new(LoxValue::Boolean(is_instance)))
+                }
+                _ => Err(RuntimeEvent::Error(RuntimeError::new(
+                    operator.to_owned(),
+                    "Right operand of 'is' must be a class.".to_string(),
+                ))),
+            },
+
+            // Equality operations
+            TokenType::BangEqual => Ok(Rc::new(LoxValue::Boolean(
+                !self.equals(left, right, operator)?,
+            ))),
+            TokenType::EqualEqual => Ok(Rc::new(LoxValue::Boolean(
+                self.equals(left, right, operator)?,
+            ))),
+            _ => Err(RuntimeEvent::Error(RuntimeError::new(
+                operator.to_owned(),
+                "Invalid binary operator.".to_string(),
+            ))),
+        }
+    }
+
+    fn index(
+        &self,
+        object: &LoxValue,
+        bracket: &Token,
+        index: &Rc<LoxValue>,
+    ) -> Result<Rc<LoxValue>, RuntimeEvent> {
+        match object {
+            LoxValue::List(elements) => {
+                let elements = elements.borrow();
+                let position = list_position(bracket, index, elements.len())?;
+                Ok(elements[position].clone())
+            }
+            LoxValue::Map(entries) => entries
+                .borrow()
+                .iter()
+                .find(|(key, _)| key.eq(index))
+                .map(|(_, value)| value.clone())
+                .ok_or_else(|| {
+                    RuntimeEvent::Error(RuntimeError::new(
+                        bracket.to_owned(),
+                        format!("Undefined key '{}'.", index.stringify()),
+                    ))
+                }),
+            _ => Err(RuntimeEvent::Error(RuntimeError::new(
+                bracket.to_owned(),
+                "Only lists and maps can be indexed.".to_string(),
+            ))),
+        }
+    }
+
+    fn set_index(
+        &self,
+        object: &LoxValue,
+        bracket: &Token,
+        index: Rc<LoxValue>,
+        value: Rc<LoxValue>,
+    ) -> Result<(), RuntimeEvent> {
+        match object {
+            LoxValue::List(elements) => {
+                let mut elements = elements.borrow_mut();
+                let position = list_position(bracket, &index, elements.len())?;
+                elements[position] = value;
+            }
+            LoxValue::Map(entries) => {
+                let mut entries = entries.borrow_mut();
+                match entries.iter_mut().find(|(key, _)| key.eq(&index)) {
+                    Some(entry) => entry.1 = value,
+                    None => entries.push((index, value)),
+                }
+            }
+            _ => {
+                return Err(RuntimeEvent::Error(RuntimeError::new(
+                    bracket.to_owned(),
+                    "Only lists and maps can be indexed.".to_string(),
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    // Both operands as numbers, if the operation can treat them as such in the current mode
+    fn number_operands(&self, left: &LoxValue, right: &LoxValue) -> Option<(f64, f64)> {
+        match (left, right) {
+            (LoxValue::Number(left_num), LoxValue::Number(right_num)) => {
+                Some((*left_num, *right_num))
+            }
+            _ if self.config.loose_coercion => {
+                Some((try_coerce_number(left)?, try_coerce_number(right)?))
+            }
+            _ => None,
+        }
+    }
+
+    // Returns the value of the last statement if it's an expression. A runtime error stops the
+    // program, it's reported to the error output and returned as well.
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<Option<Rc<LoxValue>>, RuntimeError> {
+        self.execute(statements).inspect_err(|err| {
+            error::runtime_error(err, self.err.get_mut().as_mut());
+        })
+    }
+
+    // Resolves and runs statements built by the caller rather than parsed from source,
+    // returning the first error instead of reporting it. Resolving is repeatable, so
+    // statements that were resolved already are fine as well.
+    pub fn eval_statements(&mut self, mut statements: Vec<Stmt>) -> Result<(), RuntimeError> {
+        let (_, diagnostics) = error::collect_diagnostics(|| {
+            statements.resolve(&mut Resolver::for_config(&self.config))
+        });
+        let resolve_error = diagnostics
+            .into_iter()
+            .find(|diagnostic| diagnostic.severity == Severity::Error);
+        if let Some(diagnostic) = resolve_error {
+            // Resolver diagnostics have their token, the line is a fallback for any that don't
+            let token = diagnostic
+                .token
+                .unwrap_or_else(|| Token::new(TokenType::Eof, "", diagnostic.line));
+            return Err(RuntimeError::new(token, diagnostic.message));
+        }
+
+        self.execute(&statements).map(|_| ())
+    }
+
+    // Runs the source in one go for embedding, see `rlox::run`. The errors of every phase are
+    // returned rather than reported, and the error flags are left alone.
+    pub fn interpret_str(&mut self, source: &str) -> Result<(), Vec<LoxError>> {
+        crate::run(self, source).map(|_| ())
+    }
+
+    // Like `interpret`, but returns the error instead of reporting it
+    pub(crate) fn execute(
+        &mut self,
+        statements: &[Stmt],
+    ) -> Result<Option<Rc<LoxValue>>, RuntimeError> {
+        let mut last_value = None;
+        for statement in statements {
+            let result = match statement {
+                Stmt::Expression { expr } => {
+                    expr.evaluate(self, self.environment.clone()).map(Some)
+                }
+                _ => statement
+                    .evaluate(self, self.environment.clone())
+                    .map(|_| None),
+            };
+
+            last_value = match result {
+                Ok(value) => value,
+                Err(RuntimeEvent::Error(err)) => return Err(err),
+                // The resolver rejects these already, but they must never escape the
+                // interpreter, e.g. for statements that did not go through the resolver
+                Err(RuntimeEvent::Return { keyword, .. }) => {
+                    return Err(RuntimeError::new(
+                        keyword,
+                        "Can't return from top-level code.".to_string(),
+                    ))
+                }
+                Err(RuntimeEvent::Break { keyword } | RuntimeEvent::Continue { keyword }) => {
+                    let message = format!("Can't use '{}' outside of a loop.", keyword.lexeme);
+                    return Err(RuntimeError::new(keyword, message));
+                }
+                Err(RuntimeEvent::Thrown { keyword, value }) => {
+                    let message = format!("Uncaught exception: {}.", value.stringify());
+                    return Err(RuntimeError::new(keyword, message));
+                }
+            };
+        }
+        Ok(last_value)
+    }
+}
+
+// The field or bound method of a value
+fn property(object: &Rc<LoxValue>, name: &Token) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    match object.as_ref() {
+        LoxValue::Instance(lox_instance) => LoxInstance::get(lox_instance.clone(), name),
+        // Built-in values have methods, but no fields
+        _ => natives::method(object, name.lexeme.symbol()).ok_or_else(|| {
+            RuntimeEvent::Error(RuntimeError::new(
+                name.to_owned(),
+                "Only instances have properties.".to_string(),
+            ))
+        }),
+    }
+}
+
+// The token to report that a superclass or mixin isn't a class at. The parser only gives them as
+// variables, statements built otherwise report it at the name of the class.
+fn operand_token<'a>(operand: &'a Expr, class_name: &'a Token) -> &'a Token {
+    match operand {
+        Expr::Variable { name, .. } => name,
+        _ => class_name,
+    }
+}
+
+// Whether a logical operator skips its right operand, None for other operators. The logical
+// assignments `||=` and `&&=` skip assigning the same way `or` and `and` do.
+fn short_circuits(operator: &Token, left: &LoxValue) -> Option<bool> {
+    match operator.token_type {
+        TokenType::Or | TokenType::QuestionColon | TokenType::PipePipeEqual => {
+            Some(left.is_truthy())
+        }
+        TokenType::And | TokenType::AmpAmpEqual => Some(!left.is_truthy()),
+        _ => None,
+    }
+}
+
+// Only numbers have an order. Booleans get their own message, as reference Lox rejects them
+// too but treating them as 1 and 0 would be a plausible guess.
+fn comparison_error(operator: &Token, left: &LoxValue, right: &LoxValue) -> RuntimeEvent {
+    let message = match (left, right) {
+        (LoxValue::Boolean(_), _) | (_, LoxValue::Boolean(_)) => {
+            format!("Can't compare booleans with '{}'.", operator.lexeme)
+        }
+        _ => "Operands must be numbers.".to_string(),
+    };
+    RuntimeEvent::Error(RuntimeError::new(operator.to_owned(), message))
+}
+
+// Shifts work on the value as a 64-bit two's complement integer: `<<` drops the bits shifted
+// out, `>>` keeps the sign and `>>>` shifts in zeros, reading the bits as unsigned. The result
+// is a number again, so beyond 2^53 it's rounded like any other.
+fn shift(operator: &Token, value: f64, amount: f64) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    let error = |message: &str| {
+        Err(RuntimeEvent::Error(RuntimeError::new(
+            operator.to_owned(),
+            message.to_string(),
+        )))
+    };
+
+    // i64::MIN is exactly representable, i64::MAX isn't, so the upper bound is exclusive
+    if value.fract() != 0.0 || !(-(2f64.powi(63))..2f64.powi(63)).contains(&value) {
+        return error("Shifted value must be a 64-bit integer.");
+    }
+    if amount.fract() != 0.0 || !(0.0..=63.0).contains(&amount) {
+        return error("Shift amount must be an integer from 0 to 63.");
+    }
+
+    let (value, amount) = (value as i64, amount as u32);
+    let result = match operator.token_type {
+        TokenType::LessLess => (value << amount) as f64,
+        TokenType::GreaterGreater => (value >> amount) as f64,
+        _ => ((value as u64) >> amount) as f64,
+    };
+    Ok(Rc::new(LoxValue::Number(result)))
+}
+
+// The methods of a mixin are flattened into the class mixing it in, so `super` in them refers
+// to that class's superclass rather than the mixin's own. The methods of every class close over
+// a scope binding just `super`, which is swapped for one binding the class's superclass, or nil
+// if it has none.
+fn rebind_super(method: &Rc<LoxValue>, superclass: Option<&Rc<LoxValue>>) -> Rc<LoxValue> {
+    let LoxValue::Callable(LoxCallable::Function {
+        declaration,
+        closure,
+        is_initializer,
+    }) = method.as_ref()
+    else {
+        return method.clone();
+    };
+    let Some(enclosing) = closure.borrow().enclosing().cloned() else {
+        return method.clone();
+    };
+
+    let mut super_env = Environment::new_enclosing(enclosing);
+    super_env.define(
+        Symbol::SUPER,
+        superclass
+            .cloned()
+            .unwrap_or_else(|| Rc::new(LoxValue::Nil)),
+    );
+    Rc::new(LoxValue::Callable(LoxCallable::new_function(
+        declaration.clone(),
+        Rc::new(RefCell::new(super_env)),
+        *is_initializer,
+    )))
+}
+
+// Checks that the index is a whole number within the bounds of a list
+fn list_position(bracket: &Token, index: &LoxValue, length: usize) -> Result<usize, RuntimeEvent> {
+    let index = match index {
+        LoxValue::Number(index) if index.fract() == 0.0 => *index,
+        _ => {
+            return Err(RuntimeEvent::Error(RuntimeError::new(
+                bracket.to_owned(),
+                "List index must be an integer.".to_string(),
+            )))
+        }
+    };
+
+    if index < 0.0 || index >= length as f64 {
+        return Err(RuntimeEvent::Error(RuntimeError::new(
+            bracket.to_owned(),
+            format!(
+                "List index {} is out of bounds for length {}.",
+                format_number(index),
+                length
+            ),
+        )));
+    }
+    Ok(index as usize)
+}
+
+pub trait Evaluatable<T> {
+    fn evaluate(
+        &self,
+        interpreter: &Interpreter,
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<T, RuntimeEvent>;
+}
+
+impl Evaluatable<()> for Stmt {
+    fn evaluate(
+        &self,
+        interpreter: &Interpreter,
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<(), RuntimeEvent> {
+        match self {
+            Stmt::Expression { expr } => {
+                expr.evaluate(interpreter, environment)?;
+                Ok(())
+            }
+            Stmt::Print { expr } => {
+                let value = expr.evaluate(interpreter, environment)?;
+                interpreter.print(&value);
+                Ok(())
+            }
+            Stmt::Eprint { expr } => {
+                let value = expr.evaluate(interpreter, environment)?;
+                writeln!(interpreter.err.borrow_mut(), "{}", value.stringify())
+                    .expect("Cannot write to error output");
+                Ok(())
+            }
+            Stmt::Assert {
+                keyword,
+                condition,
+                message,
+            } => {
+                if condition
+                    .evaluate(interpreter, environment.clone())?
+                    .is_truthy()
+                {
+                    return Ok(());
+                }
+
+                let message = match message {
+                    Some(message) => format!(
+                        "Assertion failed: {}",
+                        message.evaluate(interpreter, environment)?.stringify()
+                    ),
+                    None => "Assertion failed.".to_string(),
+                };
+                Err(RuntimeEvent::Error(RuntimeError::new(
+                    keyword.clone(),
+                    message,
+                )))
+            }
+            // Sugar for a read-only instance holding one instance of the enum's class per variant,
+            // which are read-only as well and only equal to themselves
+            Stmt::Enum { name, variants } => {
+                let class = Rc::new(LoxClass::new(name.lexeme.symbol(), None, vec![]));
+                let instance = |label: String, fields| {
+                    let instance = LoxInstance::read_only(class.clone(), label, fields);
+                    Rc::new(LoxValue::Instance(Rc::new(RefCell::new(instance))))
+                };
+
+                let variants = variants
+                    .iter()
+                    .map(|variant| {
+                        let label = format!("{}.{}", name.lexeme, variant.lexeme);
+                        (variant.lexeme.symbol(), instance(label, HashMap::new()))
+                    })
+                    .collect();
+                let enumeration = instance(format!("<enum {}>", name.lexeme), variants);
+
+                environment
+                    .borrow_mut()
+                    .define(name.lexeme.symbol(), enumeration);
+                Ok(())
+            }
+            Stmt::Var { name, initializer } => {
+                let mut value = match interpreter.config.strict_uninitialized {
+                    true => Rc::new(LoxValue::Uninitialized),
+                    false => Rc::new(LoxValue::Nil),
+                };
+
+                if let Some(expr) = initializer {
+                    value = expr.evaluate(interpreter, environment.clone())?;
+                }
+
+                environment.borrow_mut().define(name.lexeme.symbol(), value);
+                Ok(())
+            }
+            Stmt::VarDestructure {
+                bracket,
+                names,
+                initializer,
+            } => {
+                let value = initializer.evaluate(interpreter, environment.clone())?;
+                let elements = match value.as_ref() {
+                    LoxValue::List(elements) => elements.borrow().clone(),
+                    _ => {
+                        return Err(RuntimeEvent::Error(RuntimeError::new(
+                            bracket.to_owned(),
+                            "Can only destructure lists.".to_string(),
+                        )))
+                    }
+                };
+
+                if elements.len() != names.len() {
+                    return Err(RuntimeEvent::Error(RuntimeError::new(
+                        bracket.to_owned(),
+                        format!(
+                            "Expected {} values to destructure but got {}.",
+                            names.len(),
+                            elements.len()
+                        ),
+                    )));
+                }
+
+                for (name, element) in names.iter().zip(elements) {
+                    environment
+                        .borrow_mut()
+                        .define(name.lexeme.symbol(), element);
+                }
+                Ok(())
+            }
+            Stmt::Incomplete { missing, .. } => Err(RuntimeEvent::Error(RuntimeError::new(
+                missing.clone(),
+                "Can't run a statement with syntax errors.".to_string(),
+            ))),
+            Stmt::Block { statements } => {
+                statements.evaluate(
+                    interpreter,
+                    Rc::new(RefCell::new(Environment::new_enclosing(environment))),
+                )?;
+                Ok(())
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if condition
+                    .evaluate(interpreter, environment.clone())?
+                    .is_truthy()
+                {
+                    then_branch.evaluate(interpreter, environment)?;
+                } else if let Some(else_statement) = else_branch {
+                    else_statement.evaluate(interpreter, environment)?;
+                }
+
+                Ok(())
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+                scope_per_iteration,
+            } => {
+                let mut environment = environment;
+
+                while condition
+                    .evaluate(interpreter, environment.clone())?
+                    .is_truthy()
+                {
+                    match body.evaluate(interpreter, environment.clone()) {
+                        Err(RuntimeEvent::Break { .. }) => break,
+                        // The increment of a desugared for loop still runs
+                        Ok(()) | Err(RuntimeEvent::Continue { .. }) => {}
+                        Err(other) => return Err(other),
+                    }
+
+                    if *scope_per_iteration {
+                        let next = environment.borrow().copy_scope();
+                        environment = Rc::new(RefCell::new(next));
+                    }
+                    if let Some(increment) = increment {
+                        increment.evaluate(interpreter, environment.clone())?;
+                    }
+                }
+
+                Ok(())
+            }
+            Stmt::ForIn {
+                name,
+                keyword,
+                iterable,
+                body,
+            } => {
+                let elements = match iterable
+                    .evaluate(interpreter, environment.clone())?
+                    .as_ref()
+                {
+                    LoxValue::List(elements) => elements.borrow().clone(),
+                    LoxValue::String(string) => natives::characters(string),
+                    _ => {
+                        return Err(RuntimeEvent::Error(RuntimeError::new(
+                            keyword.clone(),
+                            "Can only iterate over lists and strings.".to_string(),
+                        )))
+                    }
+                };
+
+                // Every element gets its own scope, so closures capture that element
+                for element in elements {
+                    let mut scope = Environment::new_enclosing(environment.clone());
+                    scope.define(name.lexeme.symbol(), element);
+                    match body.evaluate(interpreter, Rc::new(RefCell::new(scope))) {
+                        Err(RuntimeEvent::Break { .. }) => break,
+                        Ok(()) | Err(RuntimeEvent::Continue { .. }) => {}
+                        Err(other) => return Err(other),
+                    }
+                }
+
+                Ok(())
+            }
+            Stmt::Function { name, params, body } => {
+                let function = LoxValue::Callable(LoxCallable::new_function(
+                    Rc::new(FunctionStmt {
+                        name: Some(name.clone()),
+                        params: params.clone(),
+                        body: body.clone(),
+                    }),
+                    environment.clone(),
+                    false,
+                ));
+
+                environment
+                    .borrow_mut()
+                    .define(name.lexeme.symbol(), Rc::new(function));
+
+                Ok(())
+            }
+            Stmt::Break { keyword } => Err(RuntimeEvent::Break {
+                keyword: keyword.clone(),
+            }),
+            Stmt::Continue { keyword } => Err(RuntimeEvent::Continue {
+                keyword: keyword.clone(),
+            }),
+            Stmt::Switch {
+                keyword,
+                value,
+                cases,
+                default,
+            } => {
+                let value = value.evaluate(interpreter, environment.clone())?;
+
+                let mut body = default.as_ref();
+                for (case, case_body) in cases {
+                    let case = case.evaluate(interpreter, environment.clone())?;
+                    if interpreter.equals(&value, &case, keyword)? {
+                        body = Some(case_body);
+                        break;
+                    }
+                }
+
+                let Some(body) = body else {
+                    return Ok(());
+                };
+                let result = body.evaluate(
+                    interpreter,
+                    Rc::new(RefCell::new(Environment::new_enclosing(environment))),
+                );
+                match result {
+                    // Ends the switch only, a continue goes on to the enclosing loop
+                    Err(RuntimeEvent::Break { .. }) => Ok(()),
+                    other => other,
+                }
+            }
+            Stmt::Throw { keyword, value } => Err(RuntimeEvent::Thrown {
+                keyword: keyword.clone(),
+                value: value.evaluate(interpreter, environment)?,
+            }),
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                let scope = || {
+                    Rc::new(RefCell::new(Environment::new_enclosing(
+                        environment.clone(),
+                    )))
+                };
+
+                let result = match (body.evaluate(interpreter, scope()), catch) {
+                    (Err(RuntimeEvent::Thrown { value, .. }), Some((name, handler))) => {
+                        let handler_scope = scope();
+                        handler_scope
+                            .borrow_mut()
+                            .define(name.lexeme.symbol(), value);
+                        handler.evaluate(interpreter, handler_scope)
+                    }
+                    (result, _) => result,
+                };
+
+                // Runs before the result propagates, be it a return, break or thrown value. One
+                // of its own replaces that result, like in Java.
+                if let Some(finally) = finally {
+                    finally.evaluate(interpreter, scope())?;
+                }
+
+                result
+            }
+            Stmt::Return { keyword, value } => {
+                let value = match value {
+                    Some(v) => v.evaluate(interpreter, environment)?,
+                    None => Rc::new(LoxValue::Nil),
+                };
+
+                Err(RuntimeEvent::Return {
+                    keyword: keyword.clone(),
+                    value,
+                })
+            }
+            Stmt::Class {
+                name,
+                superclass,
+                mixins,
+                methods,
+            } => {
+                let superclass = if let Some(superclass_expr) = superclass {
+                    let superclass_value =
+                        superclass_expr.evaluate(interpreter, environment.clone())?;
+
+                    if !matches!(
+                        superclass_value.as_ref(),
+                        LoxValue::Callable(LoxCallable::Class { .. })
+                    ) {
+                        return Err(RuntimeEvent::Error(RuntimeError::new(
+                            operand_token(superclass_expr, name).clone(),
+                            "Superclass must be a class.".to_string(),
+                        )));
+                    }
+
+                    Some(superclass_value)
+                } else {
+                    None
+                };
+
+                let mut mixin_methods = vec![];
+                for mixin in mixins {
+                    let mixin_value = mixin.evaluate(interpreter, environment.clone())?;
+                    let LoxValue::Callable(LoxCallable::Class { class: mixin_class }) =
+                        mixin_value.as_ref()
+                    else {
+                        return Err(RuntimeEvent::Error(RuntimeError::new(
+                            operand_token(mixin, name).clone(),
+                            "Mixin must be a class.".to_string(),
+                        )));
+                    };
+
+                    // Only the methods the mixin declares itself, not inherited ones
+                    for &method_name in mixin_class.method_names() {
+                        let method = mixin_class
+                            .find_method(method_name)
+                            .expect("Declared methods can be found");
+                        mixin_methods
+                            .push((method_name, rebind_super(&method, superclass.as_ref())));
+                    }
+                }
+
+                {
+                    // Bind the own classname (in the beginning this is Nil, as class is not fully initialized)
+                    let mut env_mut = environment.borrow_mut();
+                    env_mut.define(name.lexeme.symbol(), Rc::new(LoxValue::Nil));
+                }
+
+                // Methods of classes without a superclass have `super` too, in case they are mixed
+                // in, see `rebind_super`
+                let super_env = Rc::new(RefCell::new(Environment::new_enclosing(
+                    environment.clone(),
+                )));
+                super_env.borrow_mut().define(
+                    Symbol::SUPER,
+                    superclass.clone().unwrap_or_else(|| Rc::new(LoxValue::Nil)),
+                );
+
+                let methods = methods
+                    .iter()
+                    .map(|method| match method {
+                        Stmt::Function { name, params, body } => Ok((
+                            name.lexeme.symbol(),
+                            Rc::new(LoxValue::Callable(LoxCallable::new_function(
+                                Rc::new(FunctionStmt {
+                                    name: Some(name.clone()),
+                                    params: params.clone(),
+                                    body: body.clone(),
+                                }),
+                                super_env.clone(),
+                                name.lexeme == Symbol::INIT,
+                            ))),
+                        )),
+                        _ => Err(RuntimeEvent::Error(RuntimeError::new(
+                            name.clone(),
+                            "Class can only contain methods.".to_string(),
+                        ))),
+                    })
+                    .collect::<Result<Vec<(Symbol, Rc<LoxValue>)>, RuntimeEvent>>()?;
+                let methods = mixin_methods.into_iter().chain(methods).collect();
+
+                let class = LoxValue::Callable(LoxCallable::Class {
+                    class: Rc::new(LoxClass::new(name.lexeme.symbol(), superclass, methods)),
+                });
+
+                environment.borrow_mut().assign(name, Rc::new(class))?;
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Evaluatable<()> for Vec<Stmt> {
+    fn evaluate(
+        &self,
+        interpreter: &Interpreter,
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<(), RuntimeEvent> {
+        // Functions are hoisted, so they can be called before their declaration in the block
+        let is_function = |statement: &&Stmt| matches!(statement, Stmt::Function { .. });
+        let functions = self.iter().filter(is_function);
+        let statements = self.iter().filter(|statement| !is_function(statement));
+
+        for statement in functions.chain(statements) {
+            statement.evaluate(interpreter, environment.clone())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Evaluatable<Rc<LoxValue>> for Expr {
+    fn evaluate(
+        &self,
+        interpreter: &Interpreter,
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<Rc<LoxValue>, RuntimeEvent> {
+        match self {
+            Expr::Literal { value } => Ok(Rc::new(LoxValue::from(value))),
+            Expr::Grouping { expression } => expression.evaluate(interpreter, environment),
+            Expr::Unary { operator, right } => {
+                let right = right.evaluate(interpreter, environment)?;
+                let right = right.as_ref();
+
+                match operator.token_type {
+                    TokenType::Minus => match right {
+                        LoxValue::Number(num) => Ok(Rc::new(LoxValue::Number(-num))),
+                        _ => Err(RuntimeEvent::Error(RuntimeError::new(
+                            operator.to_owned(),
+                            "Cannot negate non numeric value".to_string(),
+                        ))),
+                    },
+                    TokenType::Bang => Ok(Rc::new(LoxValue::Boolean(!right.is_truthy()))),
+                    _ => panic!("Invalid unary expression in AST"),
+                }
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left = left.evaluate(interpreter, environment.clone())?;
+                let right = right.evaluate(interpreter, environment)?;
+                interpreter.binary(&left, operator, &right)
+            }
+            Expr::Variable { name, depth } => match depth {
+                // Locals are always defined, the resolver made sure of that
+                None if interpreter.config.nil_for_undefined_globals => {
+                    environment.borrow().get_global_or_nil(name)
+                }
+                _ => environment.borrow().get_at(*depth, name),
+            },
+            Expr::Assign { name, value, depth } => {
+                let value = value.evaluate(interpreter, environment.clone())?;
+                environment
+                    .borrow_mut()
+                    .assign_at(*depth, name, value.clone())?;
+                Ok(value)
+            }
             Expr::Conditional {
                 condition,
                 then,
                 r#else,
             } => {
-                let condition = condition.evaluate(environment.clone())?;
+                let condition = condition.evaluate(interpreter, environment.clone())?;
 
                 if condition.is_truthy() {
-                    then.evaluate(environment)
+                    then.evaluate(interpreter, environment)
                 } else {
-                    r#else.evaluate(environment)
+                    r#else.evaluate(interpreter, environment)
                 }
             }
             Expr::Logical {
@@ -474,59 +1376,105 @@ impl Evaluatable<Rc<LoxValue>> for Expr {
                 operator,
                 right,
             } => {
-                let left = left.evaluate(environment.clone())?;
+                let left = left.evaluate(interpreter, environment.clone())?;
 
-                match operator.token_type {
-                    TokenType::Or => {
-                        if left.is_truthy() {
-                            return Ok(left);
-                        }
-                    }
-                    TokenType::And => {
-                        if !left.is_truthy() {
-                            return Ok(left);
-                        }
-                    }
-                    _ => {
-                        return Err(RuntimeEvent::Error(RuntimeError::new(
-                            operator.to_owned(),
-                            "Invalid Logical operator.".to_string(),
-                        )))
-                    }
+                let Some(short_circuits) = short_circuits(operator, &left) else {
+                    return Err(RuntimeEvent::Error(RuntimeError::new(
+                        operator.to_owned(),
+                        "Invalid Logical operator.".to_string(),
+                    )));
+                };
+
+                let result = if short_circuits {
+                    left
+                } else {
+                    right.evaluate(interpreter, environment)?
+                };
+
+                // Unlike `or` and `and`, the Elvis operator and the logical assignments (see
+                // `Parser::assignment`) are meant to pick one of the values
+                if interpreter.config.strict_booleans
+                    && matches!(operator.token_type, TokenType::Or | TokenType::And)
+                {
+                    Ok(Rc::new(LoxValue::Boolean(result.is_truthy())))
+                } else {
+                    Ok(result)
+                }
+            }
+            Expr::Call {
+                callee,
+                closing_paren,
+                arguments,
+            } => {
+                let callee = callee.evaluate(interpreter, environment.clone())?;
+
+                let mut evaluated_args = VecDeque::new();
+                for arg in arguments {
+                    evaluated_args.push_back(arg.evaluate(interpreter, environment.clone())?);
                 }
 
-                right.evaluate(environment)
+                interpreter.call(&callee, evaluated_args, closing_paren)
             }
-            Expr::Call {
-                callee,
+            Expr::Cascade {
+                object,
+                name,
                 closing_paren,
                 arguments,
             } => {
-                let callee = callee.evaluate(environment.clone())?;
+                let object = object.evaluate(interpreter, environment.clone())?;
+                let method = property(&object, name)?;
 
                 let mut evaluated_args = VecDeque::new();
                 for arg in arguments {
-                    evaluated_args.push_back(arg.evaluate(environment.clone())?);
+                    evaluated_args.push_back(arg.evaluate(interpreter, environment.clone())?);
                 }
 
-                let function = match callee.as_ref() {
-                    LoxValue::Callable(callable) => callable,
-                    _ => {
-                        return Err(RuntimeEvent::Error(RuntimeError::new(
-                            closing_paren.to_owned(),
-                            "Can only call functions and classes.".to_string(),
-                        )))
+                // The method's result is dropped, so consecutive cascades all apply to the object
+                interpreter.call(&method, evaluated_args, closing_paren)?;
+                Ok(object)
+            }
+            Expr::List { elements } => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(element.evaluate(interpreter, environment.clone())?);
+                }
+                Ok(Rc::new(LoxValue::List(Rc::new(RefCell::new(values)))))
+            }
+            Expr::Map { entries } => {
+                let mut values: MapEntries = vec![];
+                for (key, value) in entries {
+                    let key = key.evaluate(interpreter, environment.clone())?;
+                    let value = value.evaluate(interpreter, environment.clone())?;
+                    // A repeated key overrides the earlier entry
+                    match values.iter_mut().find(|(existing, _)| existing.eq(&key)) {
+                        Some(entry) => entry.1 = value,
+                        None => values.push((key, value)),
                     }
-                };
-
-                function.call(evaluated_args, closing_paren)
+                }
+                Ok(Rc::new(LoxValue::Map(Rc::new(RefCell::new(values)))))
+            }
+            Expr::Function { params, body } => {
+                Ok(Rc::new(LoxValue::Callable(LoxCallable::new_function(
+                    Rc::new(FunctionStmt {
+                        name: None,
+                        params: params.clone(),
+                        body: body.clone(),
+                    }),
+                    environment,
+                    false,
+                ))))
             }
             Expr::Get { object, name } => {
-                let object = object.evaluate(environment)?;
+                let object = object.evaluate(interpreter, environment)?;
+                property(&object, name)
+            }
+            Expr::Delete { object, name } => {
+                let object = object.evaluate(interpreter, environment)?;
 
                 match object.as_ref() {
                     LoxValue::Instance(lox_instance) => {
-                        LoxInstance::get(lox_instance.clone(), name)
+                        let existed = LoxInstance::delete(lox_instance, name)?;
+                        Ok(Rc::new(LoxValue::Boolean(existed)))
                     }
                     _ => Err(RuntimeEvent::Error(RuntimeError::new(
                         name.to_owned(),
@@ -538,37 +1486,113 @@ impl Evaluatable<Rc<LoxValue>> for Expr {
                 object,
                 name,
                 value,
+                operator,
             } => {
-                let object = object.evaluate(environment.clone())?;
+                let object = object.evaluate(interpreter, environment.clone())?;
 
                 match object.as_ref() {
                     LoxValue::Instance(lox_instance) => {
-                        let value = value.evaluate(environment)?;
-                        lox_instance.borrow_mut().set(name, value.clone())?;
+                        // Compound assignments read the current value before evaluating the
+                        // value, like `a += b` does
+                        let current = match operator {
+                            Some(_) => Some(LoxInstance::get(lox_instance.clone(), name)?),
+                            None => None,
+                        };
+                        // `||=` and `&&=` only evaluate the value if the current one doesn't decide
+                        if let (Some(operator), Some(current)) = (operator, &current) {
+                            if short_circuits(operator, current) == Some(true) {
+                                return Ok(current.clone());
+                            }
+                        }
+                        let mut value = value.evaluate(interpreter, environment)?;
+                        if let (Some(operator), Some(current)) = (operator, current) {
+                            if !operator.token_type.is_logical() {
+                                value = interpreter.binary(&current, operator, &value)?;
+                            }
+                        }
+                        LoxInstance::set(lox_instance, name, value.clone())?;
                         Ok(value)
                     }
+                    // Classes have no fields either, but this one deserves a clearer message
+                    LoxValue::Callable(LoxCallable::Class { class })
+                        if name.lexeme.as_str() == "superclass" =>
+                    {
+                        Err(RuntimeEvent::Error(RuntimeError::new(
+                            name.to_owned(),
+                            format!(
+                                "Can't change the superclass of '{}' after its declaration.",
+                                class.name
+                            ),
+                        )))
+                    }
                     _ => Err(RuntimeEvent::Error(RuntimeError::new(
                         name.to_owned(),
                         "Only instances have properties.".to_string(),
                     ))),
                 }
             }
-            Expr::This { keyword, depth } => environment.borrow().get_at(Some(*depth), keyword),
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let object = object.evaluate(interpreter, environment.clone())?;
+                let index = index.evaluate(interpreter, environment)?;
+                interpreter.index(&object, bracket, &index)
+            }
+            Expr::IndexSet {
+                object,
+                bracket,
+                index,
+                value,
+                operator,
+            } => {
+                // The object and index are evaluated once, even for compound assignments
+                let object = object.evaluate(interpreter, environment.clone())?;
+                let index = index.evaluate(interpreter, environment.clone())?;
+                // Like for properties, the current value is read before evaluating the value
+                let current = match operator {
+                    Some(_) => Some(interpreter.index(&object, bracket, &index)?),
+                    None => None,
+                };
+                if let (Some(operator), Some(current)) = (operator, &current) {
+                    if short_circuits(operator, current) == Some(true) {
+                        return Ok(current.clone());
+                    }
+                }
+                let mut value = value.evaluate(interpreter, environment)?;
+                if let (Some(operator), Some(current)) = (operator, current) {
+                    if !operator.token_type.is_logical() {
+                        value = interpreter.binary(&current, operator, &value)?;
+                    }
+                }
+                interpreter.set_index(&object, bracket, index, value.clone())?;
+                Ok(value)
+            }
+            Expr::This { keyword, depth } => environment.borrow().get_at(*depth, keyword),
             Expr::Super {
                 keyword,
                 method,
                 depth,
+                this_depth,
             } => {
                 // First get the superclass value and extend its lifetime
-                let superclass_value = environment.borrow().get_at(Some(*depth), keyword)?;
+                let superclass_value = environment.borrow().get_at(*depth, keyword)?;
                 let superclass = match superclass_value.as_ref() {
                     LoxValue::Callable(LoxCallable::Class { class }) => class,
-                    _ => panic!("Superclass must be LoxClass"),
+                    // A method of a class without superclass, also if it was mixed into one, see
+                    // `rebind_super`
+                    _ => {
+                        return Err(RuntimeEvent::Error(RuntimeError::new(
+                            keyword.clone(),
+                            "Can't use 'super' in a class with no superclass.".to_string(),
+                        )))
+                    }
                 };
 
                 let this_value = environment.borrow().get_at(
-                    Some(*depth - 1),
-                    &Token::new(TokenType::This, "this".to_string(), keyword.line),
+                    *this_depth,
+                    &Token::new(TokenType::This, Symbol::THIS, keyword.line),
                 )?;
 
                 let object = match this_value.as_ref() {
@@ -576,7 +1600,7 @@ impl Evaluatable<Rc<LoxValue>> for Expr {
                     _ => panic!("'this' while evaluating super, must be LoxInstance"),
                 };
 
-                let method_value = superclass.find_method(&method.lexeme);
+                let method_value = superclass.find_method(method.lexeme.symbol());
 
                 match method_value {
                     Some(method) => Ok(method.bind(object.clone())),
@@ -589,3 +1613,885 @@ impl Evaluatable<Rc<LoxValue>> for Expr {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::{Cell, RefCell},
+        io::Write,
+        rc::Rc,
+    };
+
+    use super::{
+        format_number, Interpreter, InterpreterConfig, LoxValue, MyPartialEq, RuntimeError,
+    };
+    use crate::{
+        error,
+        expr::Expr,
+        parser::Parser,
+        resolver::Resolvable,
+        resolver::Resolver,
+        scanner::Scanner,
+        stmt::Stmt,
+        symbol::Symbol,
+        token::{Literal, Token},
+        token_type::TokenType,
+    };
+
+    // Writer the test keeps a handle to, to inspect what the interpreter wrote
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl SharedBuffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).expect("Output should be UTF-8")
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run(interpreter: &mut Interpreter, source: &str) {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let mut statements = Parser::new(tokens).parse().expect("Source should parse");
+        statements.resolve(&mut Resolver::for_config(interpreter.config()));
+        // The tests look at the error output instead
+        let _ = interpreter.interpret(&statements);
+    }
+
+    fn global(interpreter: &Interpreter, name: &str) -> Rc<LoxValue> {
+        let value = interpreter
+            .environment
+            .borrow()
+            .get_at(Some(0), &Token::new(TokenType::Identifier, name, 0));
+
+        match value {
+            Ok(value) => value,
+            Err(_) => panic!("Global '{}' is not defined", name),
+        }
+    }
+
+    #[test]
+    fn test_top_level_return_is_reported_instead_of_panicking() {
+        let tokens = Scanner::new("return 1;".to_string()).scan_tokens();
+        let statements = Parser::new(tokens).parse().expect("Source should parse");
+
+        // Skip the resolver (which rejects this) to make the return reach the interpreter
+        let result = Interpreter::default().interpret(&statements);
+
+        assert!(error::had_runtime_error());
+        let err = result.expect_err("Return from top-level code should fail");
+        assert_eq!(err.message, "Can't return from top-level code.");
+    }
+
+    #[test]
+    fn test_class_with_non_method_is_reported_instead_of_panicking() {
+        // The parser never puts anything but methods into a class body
+        let broken_class = || Stmt::Class {
+            name: Token::new(TokenType::Identifier, "Broken", 1),
+            superclass: None,
+            mixins: vec![],
+            methods: vec![Stmt::Print {
+                expr: Expr::Literal {
+                    value: Literal::Nil,
+                },
+            }],
+        };
+
+        let (_, diagnostics) =
+            error::collect_diagnostics(|| vec![broken_class()].resolve(&mut Resolver::new()));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Class can only contain methods.");
+
+        let err = SharedBuffer::default();
+        let mut interpreter = Interpreter::with_writers(
+            InterpreterConfig::default(),
+            Box::new(std::io::sink()),
+            Box::new(err.clone()),
+        );
+        assert!(interpreter.interpret(&[broken_class()]).is_err());
+        assert_eq!(
+            err.contents(),
+            "[line 1]: Class can only contain methods.\n"
+        );
+    }
+
+    #[test]
+    fn test_class_with_non_variable_operands_is_reported_instead_of_panicking() {
+        // The parser only gives superclasses and mixins as variables
+        let class = |superclass, mixins| Stmt::Class {
+            name: Token::new(TokenType::Identifier, "Broken", 1),
+            superclass,
+            mixins,
+            methods: vec![],
+        };
+        let number = || Expr::Literal {
+            value: Literal::Number(1.0),
+        };
+
+        for (statement, message) in [
+            (class(Some(number()), vec![]), "Superclass must be a class."),
+            (class(None, vec![number()]), "Mixin must be a class."),
+        ] {
+            let err = Interpreter::default()
+                .interpret(&[statement])
+                .expect_err("Should fail");
+            assert_eq!(err.message, message);
+            assert_eq!(err.token.lexeme.as_str(), "Broken");
+        }
+    }
+
+    // An interpreter writing its output and error output into the returned buffers
+    fn with_buffers(config: InterpreterConfig) -> (Interpreter, SharedBuffer, SharedBuffer) {
+        let out = SharedBuffer::default();
+        let err = SharedBuffer::default();
+        let interpreter =
+            Interpreter::with_writers(config, Box::new(out.clone()), Box::new(err.clone()));
+        (interpreter, out, err)
+    }
+
+    fn run_with_errors(config: InterpreterConfig, source: &str) -> (String, String) {
+        let (mut interpreter, out, err) = with_buffers(config);
+        run(&mut interpreter, source);
+        (out.contents(), err.contents())
+    }
+
+    #[test]
+    fn test_interpret_str_runs_the_whole_pipeline() {
+        let (mut interpreter, out, err) = with_buffers(InterpreterConfig::default());
+
+        assert!(interpreter.interpret_str("print 1+1;").is_ok());
+        assert_eq!(out.contents(), "2\n");
+
+        let errors = interpreter
+            .interpret_str("print 1 +;\nprint nil - 1;")
+            .expect_err("Should fail to parse");
+        assert_eq!(errors.len(), 1);
+        let errors = interpreter
+            .interpret_str("print nil - 1;")
+            .expect_err("Should fail to run");
+        assert_eq!(errors[0].to_string(), "[line 1]: Operands must be numbers.");
+        assert_eq!(err.contents(), "");
+        assert!(!error::had_error() && !error::had_runtime_error());
+    }
+
+    #[test]
+    fn test_uninitialized_variables_are_nil_by_default() {
+        let (out, err) = run_with_errors(
+            InterpreterConfig::default(),
+            "var a;\nprint a;\na = 1;\nprint a;",
+        );
+
+        assert_eq!(out, "nil\n1\n");
+        assert_eq!(err, "");
+    }
+
+    #[test]
+    fn test_strict_uninitialized_mode_errors_on_read_until_assigned() {
+        let strict = InterpreterConfig {
+            strict_uninitialized: true,
+            ..Default::default()
+        };
+        let (out, err) = run_with_errors(strict, "var a;\na = 1;\nprint a;\nvar b;\nprint b;");
+
+        assert_eq!(out, "1\n");
+        assert_eq!(err, "[line 5]: Use of uninitialized variable 'b'.\n");
+    }
+
+    #[test]
+    fn test_sprintf_allows_as_much_precision_as_to_fixed() {
+        let (out, err) = run_with_errors(
+            InterpreterConfig::default(),
+            "print sprintf(\"%.100f\", 1) == (1).toFixed(100);\nprint sprintf(\"%.70000f\", 1);",
+        );
+
+        assert_eq!(out, "true\n");
+        assert_eq!(
+            err,
+            "[line 2]: sprintf() doesn't support the specifier '%.70000f'.\n"
+        );
+    }
+
+    #[test]
+    fn test_comparing_nil_is_an_error() {
+        for source in ["nil < 1;", "1 >= nil;", "nil <= nil;"] {
+            for config in [
+                InterpreterConfig::default(),
+                InterpreterConfig {
+                    loose_coercion: true,
+                    ..Default::default()
+                },
+            ] {
+                let (_, err) = run_with_errors(config, source);
+                assert_eq!(err, "[line 1]: Operands must be numbers.\n", "{}", source);
+            }
+        }
+
+        // `compare()` orders nil before everything else instead
+        let (out, err) = run_with_errors(
+            InterpreterConfig::default(),
+            "print compare(nil, 1);\nprint compare(nil, nil);",
+        );
+        assert_eq!(out, "-1\n0\n");
+        assert_eq!(err, "");
+    }
+
+    #[test]
+    fn test_superclass_cant_be_reassigned() {
+        let out = SharedBuffer::default();
+        let err = SharedBuffer::default();
+        let mut interpreter = Interpreter::with_writers(
+            InterpreterConfig::default(),
+            Box::new(out.clone()),
+            Box::new(err.clone()),
+        );
+
+        run(
+            &mut interpreter,
+            "class A { name() { return \"A\"; } }\nclass B { name() { return \"B\"; } }\n\
+            class C < A { name() { return super.name(); } }\nC.superclass = B;",
+        );
+        run(&mut interpreter, "print C().name();");
+
+        assert_eq!(
+            err.contents(),
+            "[line 4]: Can't change the superclass of 'C' after its declaration.\n"
+        );
+        assert_eq!(out.contents(), "A\n");
+    }
+
+    #[test]
+    fn test_enums_and_their_variants_are_read_only() {
+        let enumeration = "enum Color { RED, GREEN }\n";
+        for (change, message) in [
+            (
+                "Color.RED = Color.GREEN;",
+                "Can't change property 'RED' of read-only <enum Color>.",
+            ),
+            (
+                "Color.RED.hex = \"ff\";",
+                "Can't change property 'hex' of read-only Color.RED.",
+            ),
+            (
+                "delete Color.RED;",
+                "Can't change property 'RED' of read-only <enum Color>.",
+            ),
+        ] {
+            let source = format!("{}{}\nprint Color.RED == Color.GREEN;", enumeration, change);
+            let (out, err) = run_with_errors(InterpreterConfig::default(), &source);
+            assert_eq!(err, format!("[line 2]: {}\n", message));
+            assert_eq!(out, "");
+        }
+    }
+
+    #[test]
+    fn test_read_file_is_only_defined_with_filesystem_access() {
+        let path = std::env::temp_dir().join(format!("rlox-read-file-{}.txt", std::process::id()));
+        std::fs::write(&path, "contents\nof the file").expect("Should write the file");
+        let source = format!(
+            "print readFile({:?});",
+            path.to_str().expect("Path is UTF-8")
+        );
+
+        let (out, err) = run_with_errors(InterpreterConfig::default(), &source);
+        assert_eq!(out, "");
+        assert_eq!(err, "[line 1]: Undefined variable 'readFile'.\n");
+
+        let filesystem = || InterpreterConfig {
+            filesystem: true,
+            ..Default::default()
+        };
+        let (out, err) = run_with_errors(filesystem(), &source);
+        assert_eq!(out, "contents\nof the file\n");
+        assert_eq!(err, "");
+
+        let (_, err) = run_with_errors(filesystem(), "readFile(\"/does/not/exist\");");
+        assert!(
+            err.starts_with("[line 1]: Can't read file '/does/not/exist': "),
+            "{}",
+            err
+        );
+
+        // Smuggled into a sandboxed interpreter, it still refuses to run
+        let read_file = global(&Interpreter::new_with_config(filesystem()), "readFile");
+        let err = SharedBuffer::default();
+        let mut sandboxed = Interpreter::with_writers(
+            InterpreterConfig::default(),
+            Box::new(SharedBuffer::default()),
+            Box::new(err.clone()),
+        );
+        sandboxed
+            .environment
+            .borrow_mut()
+            .define(Symbol::intern("readFile"), read_file);
+        run(&mut sandboxed, &source);
+        assert_eq!(err.contents(), "[line 1]: readFile() is disabled.\n");
+
+        std::fs::remove_file(path).expect("Should remove the file");
+    }
+
+    #[test]
+    fn test_initializer_returns_this_by_default() {
+        let source = "class Point {\n  init() { this.x = 1; return; }\n}\nvar p = Point();\nprint p.x;\nprint p.init() == p;";
+        let (out, err) = run_with_errors(InterpreterConfig::default(), source);
+
+        assert_eq!(out, "1\ntrue\n");
+        assert_eq!(err, "");
+    }
+
+    #[test]
+    fn test_factory_initializers_can_return_another_instance() {
+        let factory = InterpreterConfig {
+            factory_initializers: true,
+            ..Default::default()
+        };
+        let source = "var cache;\nclass Single {\n  init() { if (cache != nil) return cache; cache = this; }\n}\nprint Single() == Single();\nclass Proxy { init() { return Single(); } }\nprint Proxy();\nclass Bad { init() { return 1; } }\nBad();";
+        let (out, err) = run_with_errors(factory, source);
+
+        assert_eq!(out, "true\nSingle instance\n");
+        assert_eq!(
+            err,
+            "[line 8]: An initializer can only return an instance.\n"
+        );
+    }
+
+    fn identifier(name: &str) -> Token {
+        Token::new(TokenType::Identifier, name, 1)
+    }
+
+    fn number(value: f64) -> Box<Expr> {
+        Box::new(Expr::Literal {
+            value: Literal::Number(value),
+        })
+    }
+
+    #[test]
+    fn test_eval_statements_resolves_and_runs_a_built_ast() {
+        // var answer; { var x = 20; answer = x + 22; }
+        let statements = vec![
+            Stmt::Var {
+                name: identifier("answer"),
+                initializer: None,
+            },
+            Stmt::Block {
+                statements: vec![
+                    Stmt::Var {
+                        name: identifier("x"),
+                        initializer: Some(*number(20.0)),
+                    },
+                    Stmt::Expression {
+                        expr: Expr::Assign {
+                            name: identifier("answer"),
+                            value: Box::new(Expr::Binary {
+                                left: Box::new(Expr::Variable {
+                                    name: identifier("x"),
+                                    depth: None,
+                                }),
+                                operator: Token::new(TokenType::Plus, "+", 1),
+                                right: number(22.0),
+                            }),
+                            depth: None,
+                        },
+                    },
+                ],
+            },
+        ];
+        let mut interpreter = Interpreter::default();
+
+        assert!(interpreter.eval_statements(statements).is_ok());
+        assert!(matches!(*global(&interpreter, "answer"), LoxValue::Number(n) if n == 42.0));
+    }
+
+    #[test]
+    fn test_eval_statements_returns_errors() {
+        let mut interpreter = Interpreter::default();
+
+        let runtime_error = interpreter.eval_statements(vec![Stmt::Expression {
+            expr: Expr::Binary {
+                left: Box::new(Expr::Literal {
+                    value: Literal::Nil,
+                }),
+                operator: Token::new(TokenType::Minus, "-", 1),
+                right: number(1.0),
+            },
+        }]);
+        assert_eq!(
+            runtime_error.err().map(|err| err.message).as_deref(),
+            Some("Operands must be numbers.")
+        );
+
+        let resolve_error = interpreter.eval_statements(vec![Stmt::Return {
+            keyword: Token::new(TokenType::Return, "return", 1),
+            value: None,
+        }]);
+        let resolve_error = resolve_error.expect_err("Should fail to resolve");
+        assert_eq!(resolve_error.message, "Can't return from top-level code.");
+        assert_eq!(resolve_error.token.token_type, TokenType::Return);
+        assert!(!error::had_runtime_error());
+    }
+
+    #[test]
+    fn test_eval_statements_resolves_in_the_modes_of_the_config() {
+        let mut interpreter = Interpreter::new_with_config(InterpreterConfig {
+            global_this: true,
+            ..Default::default()
+        });
+
+        let result = interpreter.eval_statements(vec![Stmt::Expression {
+            expr: Expr::This {
+                keyword: Token::new(TokenType::This, "this", 1),
+                depth: None,
+            },
+        }]);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_undefined_globals_error_by_default() {
+        let (out, err) = run_with_errors(InterpreterConfig::default(), "print missing;");
+
+        assert_eq!(out, "");
+        assert_eq!(err, "[line 1]: Undefined variable 'missing'.\n");
+    }
+
+    #[test]
+    fn test_undefined_globals_can_read_as_nil() {
+        let config = InterpreterConfig {
+            nil_for_undefined_globals: true,
+            ..Default::default()
+        };
+        let (out, err) = run_with_errors(
+            config,
+            "print missing;\nvar defined = 1;\nfun f() { return defined; }\nprint f();\nmissing = 2;",
+        );
+
+        assert_eq!(out, "nil\n1\n");
+        assert_eq!(err, "[line 5]: Undefined variable 'missing'.\n");
+    }
+
+    #[test]
+    fn test_incomplete_statements_refuse_to_run() {
+        let (statements, _) = error::collect_diagnostics(|| {
+            let tokens = Scanner::new("print 1\nprint 2;".to_string()).scan_tokens();
+            Parser::new(tokens).with_recovery().parse()
+        });
+        let statements = statements.expect("Recovery returns the statements");
+
+        let (mut interpreter, out, err) = with_buffers(InterpreterConfig::default());
+        assert!(interpreter.interpret(&statements).is_err());
+
+        assert_eq!(out.contents(), "");
+        assert_eq!(
+            err.contents(),
+            "[line 2]: Can't run a statement with syntax errors.\n"
+        );
+    }
+
+    #[test]
+    fn test_clock_uses_injected_source() {
+        let mut interpreter = Interpreter::default();
+        let ticks = Cell::new(0.0);
+        interpreter.set_clock(move || {
+            ticks.set(ticks.get() + 1.5);
+            ticks.get()
+        });
+
+        run(
+            &mut interpreter,
+            "var first = clock(); var second = clock();",
+        );
+
+        assert!(matches!(*global(&interpreter, "first"), LoxValue::Number(n) if n == 1.5));
+        assert!(matches!(*global(&interpreter, "second"), LoxValue::Number(n) if n == 3.0));
+    }
+
+    #[test]
+    fn test_seeded_random_numbers_are_reproducible() {
+        let source = "seedRandom(42);\nprint random();\nprint random();\n\
+            print randInt(1, 6);\nprint randInt(1, 6);\nprint randInt(1, 6);";
+        let expected = "0.7415648787718233\n0.1599103928769201\n2\n3\n1\n";
+
+        let (out, err) = run_with_errors(InterpreterConfig::default(), source);
+        assert_eq!(out, expected);
+        assert_eq!(err, "");
+
+        // Seeding from the host gives the same sequence
+        let out = SharedBuffer::default();
+        let mut interpreter = Interpreter::with_writers(
+            InterpreterConfig::default(),
+            Box::new(out.clone()),
+            Box::new(std::io::sink()),
+        );
+        interpreter.seed_random(42);
+        run(&mut interpreter, "print random();");
+        assert_eq!(out.contents(), "0.7415648787718233\n");
+    }
+
+    #[test]
+    fn test_rand_int_validates_its_bounds() {
+        for (bounds, message) in [
+            ("1.5, 2", "randInt() expects integer bounds."),
+            ("1, \"2\"", "randInt() expects integer bounds."),
+            (
+                "3, 1",
+                "randInt() expects the lower bound to be at most the upper bound.",
+            ),
+        ] {
+            let source = format!("print randInt({});", bounds);
+            let (out, err) = run_with_errors(InterpreterConfig::default(), &source);
+            assert_eq!(out, "");
+            assert_eq!(err, format!("[line 1]: {}\n", message));
+        }
+    }
+
+    #[test]
+    fn test_instant_sleep_skips_the_delay() {
+        let mut interpreter = Interpreter::new_with_config(InterpreterConfig {
+            instant_sleep: true,
+            ..Default::default()
+        });
+        let started = std::time::Instant::now();
+
+        run(&mut interpreter, "var result = sleep(60000);");
+
+        assert!(matches!(*global(&interpreter, "result"), LoxValue::Nil));
+        assert!(started.elapsed().as_secs() < 1);
+    }
+
+    #[test]
+    fn test_reset_discards_user_globals_but_keeps_natives() {
+        let mut interpreter = Interpreter::default();
+        run(&mut interpreter, "var answer = 42;");
+        assert!(matches!(*global(&interpreter, "answer"), LoxValue::Number(n) if n == 42.0));
+
+        interpreter.reset();
+
+        let answer = interpreter
+            .environment
+            .borrow()
+            .get_at(Some(0), &Token::new(TokenType::Identifier, "answer", 0));
+        assert!(answer.is_err());
+        assert!(matches!(
+            *global(&interpreter, "clock"),
+            LoxValue::Callable(_)
+        ));
+    }
+
+    #[test]
+    fn test_value_constructors_and_accessors() {
+        assert!(matches!(*LoxValue::number(1.5), LoxValue::Number(n) if n == 1.5));
+        assert!(matches!(&*LoxValue::string("text"), LoxValue::String(s) if s == "text"));
+        assert!(matches!(
+            &*LoxValue::string(String::from("owned")),
+            LoxValue::String(s) if s == "owned"
+        ));
+        assert!(matches!(*LoxValue::boolean(true), LoxValue::Boolean(true)));
+        assert!(matches!(*LoxValue::nil(), LoxValue::Nil));
+
+        assert_eq!(LoxValue::number(2.0).as_number(), Some(2.0));
+        assert_eq!(LoxValue::string("2").as_number(), None);
+        assert_eq!(LoxValue::string("text").as_string(), Some("text"));
+        assert_eq!(LoxValue::number(2.0).as_string(), None);
+        assert_eq!(LoxValue::nil().as_string(), None);
+    }
+
+    #[test]
+    fn test_repl_lines_build_on_each_other() {
+        let out = SharedBuffer::default();
+        let err = SharedBuffer::default();
+        let mut interpreter = Interpreter::with_writers(
+            InterpreterConfig::default(),
+            Box::new(out.clone()),
+            Box::new(err.clone()),
+        );
+
+        // Every line is resolved by a fresh resolver, like in the REPL. Names of earlier lines
+        // are globals to it, while locals are only ever referenced within their own line.
+        for line in [
+            "fun double(x) { return x * 2; }",
+            "print double(21);",
+            "var counter; { var count = 0; fun increment() { count = count + 1; return count; } counter = increment; }",
+            "counter();",
+            "print counter();",
+            "{ var shadowed = 1; print nil + shadowed; }",
+            "var shadowed = \"global\"; fun read() { return shadowed; }",
+            "{ var shadowed = \"local\"; print read(); }",
+        ] {
+            run(&mut interpreter, line);
+        }
+
+        assert_eq!(out.contents(), "42\n2\nglobal\n");
+        assert_eq!(
+            err.contents(),
+            "[line 1]: Operands must be two numbers or two strings.\n"
+        );
+    }
+
+    const LOGICAL_EXPRESSIONS: [&str; 4] =
+        ["1 and 2", "nil and 2", "nil or \"default\"", "false or nil"];
+
+    #[test]
+    fn test_logical_operators_return_operands_by_default() {
+        assert_eq!(
+            print_each(InterpreterConfig::default, &LOGICAL_EXPRESSIONS),
+            ["2", "nil", "default", "nil"]
+        );
+    }
+
+    #[test]
+    fn test_strict_booleans_mode_coerces_logical_results() {
+        let strict = || InterpreterConfig {
+            strict_booleans: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            print_each(strict, &LOGICAL_EXPRESSIONS),
+            ["true", "false", "true", "false"]
+        );
+    }
+
+    const CONCATENATED_VALUES: [&str; 9] = [
+        "4",
+        "2.5",
+        "-0",
+        "1000000000000000000000",
+        "0.0000001",
+        "nil",
+        "true",
+        "false",
+        "[1, nil]",
+    ];
+
+    #[test]
+    fn test_concatenation_formats_values_like_print() {
+        let printed = print_each(InterpreterConfig::default, &CONCATENATED_VALUES);
+        assert_eq!(
+            printed,
+            ["4", "2.5", "-0", "1e21", "1e-7", "nil", "true", "false", "[1, nil]"]
+        );
+
+        for template in ["\"\" + {}", "{} + \"\""] {
+            let concatenations: Vec<String> = CONCATENATED_VALUES
+                .iter()
+                .map(|value| template.replace("{}", value))
+                .collect();
+            let concatenations: Vec<&str> = concatenations.iter().map(String::as_str).collect();
+            assert_eq!(
+                print_each(InterpreterConfig::default, &concatenations),
+                printed
+            );
+        }
+    }
+
+    #[test]
+    fn test_logical_assignments_keep_their_values_in_strict_booleans_mode() {
+        let strict = InterpreterConfig {
+            strict_booleans: true,
+            ..Default::default()
+        };
+        let (out, err) = run_with_errors(
+            strict,
+            "var a;\nprint a ||= 5;\nprint a &&= \"b\";\nprint a;",
+        );
+
+        assert_eq!(out, "5\nb\nb\n");
+        assert_eq!(err, "");
+    }
+
+    #[test]
+    fn test_print_is_flushed_before_a_runtime_error() {
+        let combined = SharedBuffer::default();
+        let mut interpreter = Interpreter::with_writers(
+            InterpreterConfig::default(),
+            // Buffered, so only a flush makes the prints reach `combined`
+            Box::new(std::io::BufWriter::new(combined.clone())),
+            Box::new(combined.clone()),
+        );
+
+        run(&mut interpreter, "print 1;\nprint 2;\nnil();\nprint 3;");
+
+        assert_eq!(
+            combined.contents(),
+            "1\n2\n[line 3]: Can only call functions and classes.\n"
+        );
+    }
+
+    // A list containing itself, as `[1, [1, [1, ...]]]`
+    fn self_referential_list() -> Rc<LoxValue> {
+        let elements = Rc::new(RefCell::new(vec![Rc::new(LoxValue::Number(1.0))]));
+        let list = Rc::new(LoxValue::List(elements.clone()));
+        elements.borrow_mut().push(list.clone());
+        list
+    }
+
+    #[test]
+    fn test_comparing_self_referential_lists_terminates() {
+        let list = self_referential_list();
+        let other = self_referential_list();
+        let flat = Rc::new(LoxValue::List(Rc::new(RefCell::new(vec![
+            Rc::new(LoxValue::Number(1.0)),
+            Rc::new(LoxValue::Nil),
+        ]))));
+
+        assert!(list.eq(&list));
+        assert!(list.eq(&other));
+        assert!(!list.eq(&flat));
+    }
+
+    #[test]
+    fn test_profile_lists_every_called_function() {
+        let mut interpreter = Interpreter::new_with_config(InterpreterConfig {
+            profile: true,
+            ..Default::default()
+        });
+
+        run(
+            &mut interpreter,
+            "fun fib(n) => n < 2 ? n : fib(n - 1) + fib(n - 2);
+            fun twice(f) { f(); f(); }
+            twice(fun () => fib(5));",
+        );
+
+        let report = interpreter.profile_report().expect("Profiling is enabled");
+        let calls = |name: &str| {
+            report
+                .lines()
+                .find(|line| line.split_whitespace().next() == Some(name))
+                .and_then(|line| line.split_whitespace().nth(1))
+                .map(|calls| calls.to_string())
+        };
+
+        assert_eq!(calls("fib").as_deref(), Some("30"));
+        assert_eq!(calls("twice").as_deref(), Some("1"));
+        assert!(report.contains("<anonymous fn>"));
+        assert!(Interpreter::default().profile_report().is_none());
+    }
+
+    #[test]
+    fn test_literal_conversion() {
+        assert!(matches!(
+            LoxValue::from(&Literal::Boolean(true)),
+            LoxValue::Boolean(true)
+        ));
+        assert!(matches!(LoxValue::from(&Literal::Nil), LoxValue::Nil));
+        assert!(matches!(
+            LoxValue::from(&Literal::Number(2.5)),
+            LoxValue::Number(n) if n == 2.5
+        ));
+        assert!(matches!(
+            LoxValue::from(&Literal::String("lox".to_string())),
+            LoxValue::String(s) if s == "lox"
+        ));
+    }
+
+    // Prints every expression in a fresh interpreter, an empty output means a runtime error
+    fn print_each(config: fn() -> InterpreterConfig, expressions: &[&str]) -> Vec<String> {
+        expressions
+            .iter()
+            .map(|expression| {
+                let out = SharedBuffer::default();
+                let mut interpreter = Interpreter::with_writers(
+                    config(),
+                    Box::new(out.clone()),
+                    Box::new(std::io::sink()),
+                );
+                run(&mut interpreter, &format!("print {};", expression));
+                out.contents().trim_end().to_string()
+            })
+            .collect()
+    }
+
+    const COERCION_EXPRESSIONS: [&str; 7] = [
+        "\"3\" + 4",
+        "\"3\" + 4 == 7",
+        "\"10\" > 2",
+        "\" 2.5 \" * \"2\"",
+        "\"3\" + \"4\"",
+        "\"abc\" + 1",
+        "\"1e3\" < 5",
+    ];
+
+    #[test]
+    fn test_strict_mode_does_not_coerce_numeric_strings() {
+        assert_eq!(
+            print_each(InterpreterConfig::default, &COERCION_EXPRESSIONS),
+            vec!["34", "false", "", "", "34", "abc1", ""]
+        );
+    }
+
+    #[test]
+    fn test_loose_mode_coerces_numeric_strings() {
+        let loose = || InterpreterConfig {
+            loose_coercion: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            print_each(loose, &COERCION_EXPRESSIONS),
+            vec!["7", "true", "true", "5", "34", "abc1", ""]
+        );
+    }
+
+    fn interpret(source: &str) -> Result<Option<Rc<LoxValue>>, RuntimeError> {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let mut statements = Parser::new(tokens).parse().expect("Source should parse");
+        statements.resolve(&mut Resolver::new());
+        Interpreter::default().interpret(&statements)
+    }
+
+    #[test]
+    fn test_interpret_returns_value_of_last_expression() {
+        let value = interpret("var a = 1; a + 1;").expect("Should run");
+        assert!(value.is_some_and(|value| value.eq(&Rc::new(LoxValue::Number(2.0)))));
+
+        assert!(interpret("1 + 1; var a = 1;")
+            .expect("Should run")
+            .is_none());
+    }
+
+    #[test]
+    fn test_interpret_returns_runtime_errors() {
+        let err = interpret("var a = 1;\na + nil;").expect_err("Adding nil should fail");
+
+        assert_eq!(err.token.line, 2);
+        assert_eq!(err.message, "Operands must be two numbers or two strings.");
+    }
+
+    #[test]
+    fn test_format_number() {
+        let cases = [
+            (0.0, "0"),
+            (-0.0, "-0"),
+            (1.0, "1"),
+            (-42.0, "-42"),
+            (1.5, "1.5"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (1.0 / 3.0, "0.3333333333333333"),
+            (123456789012.0, "123456789012"),
+            (1e20, "100000000000000000000"),
+            (1e21, "1e21"),
+            (-1.5e300, "-1.5e300"),
+            (f64::MAX, "1.7976931348623157e308"),
+            (0.000001, "0.000001"),
+            (0.0000001, "1e-7"),
+            (-2.5e-10, "-2.5e-10"),
+            (f64::MIN_POSITIVE, "2.2250738585072014e-308"),
+            (f64::INFINITY, "inf"),
+            (f64::NEG_INFINITY, "-inf"),
+            (f64::NAN, "NaN"),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(format_number(value), expected, "formatting {:?}", value);
+        }
+    }
+}
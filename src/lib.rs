@@ -0,0 +1,527 @@
+use std::rc::Rc;
+
+use error::{Diagnostic, LoxError};
+use interpreter::{Interpreter, InterpreterConfig, LoxValue, RuntimeError};
+use parser::Parser;
+use resolver::{Resolvable, Resolver};
+use scanner::Scanner;
+use stmt::Stmt;
+use token::Token;
+
+pub mod ast_printer;
+pub mod constant_folding;
+pub mod environment;
+pub mod error;
+pub mod expr;
+pub mod interpreter;
+pub mod lox_callable;
+pub mod lox_class;
+pub mod lox_instance;
+pub mod natives;
+pub mod parser;
+pub mod profiler;
+pub mod resolver;
+pub mod scanner;
+pub mod stmt;
+pub mod symbol;
+pub mod token;
+pub mod token_type;
+
+// Scans the source, returning all tokens that could be scanned alongside the errors
+pub fn tokenize(source: &str) -> (Vec<Token>, Vec<Diagnostic>) {
+    error::collect_diagnostics(|| Scanner::new(source.to_string()).scan_tokens())
+}
+
+// Scans and parses the source, the statements are only returned if there were no errors
+pub fn parse(source: &str) -> (Option<Vec<Stmt>>, Vec<Diagnostic>) {
+    parse_with_config(source, &InterpreterConfig::default())
+}
+
+// The same in the modes of the config that change the syntax, like `print_function`
+pub fn parse_with_config(
+    source: &str,
+    config: &InterpreterConfig,
+) -> (Option<Vec<Stmt>>, Vec<Diagnostic>) {
+    error::collect_diagnostics(|| scan_and_parse(Scanner::new(source.to_string()), config))
+}
+
+// Resolves parsed statements in place, so they can be evaluated if no errors are returned.
+// Resolving them again gives the same depths, e.g. when a REPL resolves its history again.
+pub fn resolve(statements: &mut Vec<Stmt>) -> Vec<Diagnostic> {
+    resolve_with_config(statements, &InterpreterConfig::default())
+}
+
+// The same in the modes of the config the resolver has to allow, like `global_this`
+pub fn resolve_with_config(
+    statements: &mut Vec<Stmt>,
+    config: &InterpreterConfig,
+) -> Vec<Diagnostic> {
+    error::collect_diagnostics(|| statements.resolve(&mut Resolver::for_config(config))).1
+}
+
+// Scans, parses and resolves in the modes of the config, the statements are only returned if there
+// were no errors. The diagnostics are reported, see `error::collect_diagnostics` to get them.
+pub fn compile(
+    scanner: Scanner,
+    config: &InterpreterConfig,
+    warn_shadowing: bool,
+) -> Option<Vec<Stmt>> {
+    let mut statements = scan_and_parse(scanner, config)?;
+
+    let mut resolver = Resolver::for_config(config);
+    if !warn_shadowing {
+        resolver = resolver.without_shadowing_warnings();
+    }
+    statements.resolve(&mut resolver);
+
+    // Check again after resolution
+    match error::had_error() {
+        true => None,
+        false => Some(statements),
+    }
+}
+
+fn scan_and_parse(mut scanner: Scanner, config: &InterpreterConfig) -> Option<Vec<Stmt>> {
+    if config.print_function {
+        scanner = scanner.without_print_keyword();
+    }
+    let mut parser = Parser::new(scanner.scan_tokens());
+    if config.implicit_returns {
+        parser = parser.with_implicit_returns();
+    }
+    if config.optional_last_semicolon {
+        parser = parser.with_optional_last_semicolon();
+    }
+    let statements = parser.parse();
+
+    match error::had_error() {
+        true => None,
+        false => statements,
+    }
+}
+
+// Runs resolved statements, see `Interpreter::interpret`. Runtime errors go to the
+// interpreter's error output as well.
+pub fn evaluate(
+    interpreter: &mut Interpreter,
+    statements: &[Stmt],
+) -> Result<Option<Rc<LoxValue>>, RuntimeError> {
+    interpreter.interpret(statements)
+}
+
+// Scans, parses, resolves and runs the source against the interpreter's globals, in the modes of
+// its config, returning the errors instead of reporting them. Nothing runs if there are errors
+// before running.
+pub fn run(
+    interpreter: &mut Interpreter,
+    source: &str,
+) -> Result<Option<Rc<LoxValue>>, Vec<LoxError>> {
+    let (statements, diagnostics) = error::collect_diagnostics(|| {
+        compile(Scanner::new(source.to_string()), interpreter.config(), true)
+    });
+    let Some(statements) = statements else {
+        return Err(errors(&diagnostics));
+    };
+
+    interpreter
+        .execute(&statements)
+        .map_err(|err| vec![LoxError::from(err)])
+}
+
+// Warnings are left out
+fn errors(diagnostics: &[Diagnostic]) -> Vec<LoxError> {
+    diagnostics
+        .iter()
+        .filter_map(Diagnostic::to_error)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evaluate, parse, resolve, run, tokenize};
+    use crate::interpreter::{Interpreter, InterpreterConfig, Stringifyable};
+    use crate::parser::{Parser, DEFAULT_MAX_NESTING};
+    use crate::resolver::{Resolvable, Resolver};
+    use crate::{error, error::Diagnostic, error::LoxError, scanner::Scanner};
+    use crate::{error::Phase, error::Severity, token_type::TokenType};
+
+    #[test]
+    fn test_tokenize_invalid_input_returns_partial_tokens_and_diagnostics() {
+        let (tokens, diagnostics) = tokenize("var a = 1;\nvar b = @;\n\"unterminated");
+
+        let token_types: Vec<TokenType> = tokens
+            .iter()
+            .map(|token| token.token_type.clone())
+            .collect();
+        assert_eq!(
+            token_types,
+            [
+                TokenType::Var,
+                TokenType::Identifier,
+                TokenType::Equal,
+                TokenType::Number(1.0),
+                TokenType::Semicolon,
+                TokenType::Var,
+                TokenType::Identifier,
+                TokenType::Equal,
+                TokenType::Semicolon,
+                TokenType::Eof,
+            ]
+        );
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].line, 2);
+        assert_eq!(diagnostics[0].message, "Unexpected character: '@'.");
+        assert_eq!(diagnostics[1].line, 3);
+        assert_eq!(diagnostics[1].message, "Unterminated string.");
+    }
+
+    #[test]
+    fn test_tokenize_valid_input_has_no_diagnostics() {
+        let (tokens, diagnostics) = tokenize("print 1 + 2;");
+
+        assert_eq!(tokens.len(), 6);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_parse_reports_errors_instead_of_printing() {
+        let (statements, diagnostics) = parse("print 1 +;");
+
+        assert!(statements.is_none());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "[line 1] Error at ';': Expect expression."
+        );
+    }
+
+    #[test]
+    fn test_diagnostics_underline_the_whole_token() {
+        let source = "var a = 1;\nprint a >= ;";
+        let (_, diagnostics) = parse(source);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].underline(source).as_deref(),
+            Some("print a >= ;\n           ^")
+        );
+
+        let source = "print >= 1;";
+        let (_, diagnostics) = parse(source);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "[line 1] Error at '>=': Expect expression."
+        );
+        assert_eq!(
+            diagnostics[0].underline(source).as_deref(),
+            Some("print >= 1;\n      ^^")
+        );
+
+        // The scanner only knows the line
+        let (_, diagnostics) = tokenize("var a = @;");
+        assert_eq!(diagnostics[0].underline("var a = @;"), None);
+    }
+
+    #[test]
+    fn test_parse_returns_statements() {
+        let (statements, diagnostics) = parse("var a = 1; print a;");
+
+        assert_eq!(statements.map(|statements| statements.len()), Some(2));
+        assert!(diagnostics.is_empty());
+    }
+
+    // Parses on a thread with the stack size of a main thread, test threads get a smaller one
+    fn parse_with_main_stack(source: String) -> Vec<Diagnostic> {
+        std::thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(move || parse(&source).1)
+            .expect("Failed to spawn parser thread")
+            .join()
+            .expect("Parser thread panicked")
+    }
+
+    #[test]
+    fn test_parse_reports_too_deep_nesting() {
+        let depth = 10_000;
+        let source = format!("print {}1{};", "(".repeat(depth), ")".repeat(depth));
+
+        let diagnostics = parse_with_main_stack(source);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Expression nesting too deep.");
+    }
+
+    #[test]
+    fn test_parse_reports_too_deep_unary_nesting() {
+        let source = format!("print {}true;", "!".repeat(10_000));
+
+        let diagnostics = parse_with_main_stack(source);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Expression nesting too deep.");
+    }
+
+    #[test]
+    fn test_parse_reports_too_deep_statement_nesting() {
+        let depth = 10_000;
+        let source = format!("{}print 1;{}", "{".repeat(depth), "}".repeat(depth));
+        let diagnostics = parse_with_main_stack(source);
+        assert_eq!(diagnostics[0].message, "Nesting too deep.");
+
+        // Their conditions are nested as deeply, and reach the limit first
+        for source in [
+            format!("{}print 1;", "if (true) ".repeat(depth)),
+            format!("{}print 1;", "while (false) ".repeat(depth)),
+        ] {
+            let diagnostics = parse_with_main_stack(source);
+            assert_eq!(diagnostics[0].message, "Expression nesting too deep.");
+        }
+    }
+
+    #[test]
+    fn test_parse_accepts_nesting_up_to_the_limit() {
+        let (statements, diagnostics) = parse("print ((((1))));");
+        assert!(statements.is_some());
+        assert!(diagnostics.is_empty());
+
+        let (statements, diagnostics) = error::collect_diagnostics(|| {
+            let tokens = Scanner::new("print ((((1))));".to_string()).scan_tokens();
+            Parser::new(tokens).with_max_nesting(3).parse()
+        });
+        assert!(statements.is_none());
+        assert_eq!(diagnostics[0].message, "Expression nesting too deep.");
+    }
+
+    // Runs on a thread with the stack size of a main thread, like `parse_with_main_stack`
+    fn run_with_main_stack(source: String) -> Result<Option<String>, Vec<String>> {
+        std::thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(move || {
+                run(&mut Interpreter::default(), &source)
+                    .map(|value| value.map(|value| value.stringify()))
+                    .map_err(|errors| errors.iter().map(LoxError::to_string).collect())
+            })
+            .expect("Failed to spawn interpreter thread")
+            .join()
+            .expect("Interpreter thread panicked")
+    }
+
+    #[test]
+    fn test_every_form_runs_nested_up_to_the_limit() {
+        // Parentheses, lists, maps and calls
+        let forms = [("(", ")"), ("[", "][0]"), ("[0: ", "][0]"), ("f(", ")")];
+        let nested = |(open, close): (&str, &str), depth| {
+            let nested = format!("{}1{}", open.repeat(depth), close.repeat(depth));
+            format!("fun f(x) {{ return x; }}\n{};", nested)
+        };
+
+        for form in forms {
+            // The statement and its expression are the first two levels
+            let result = run_with_main_stack(nested(form, DEFAULT_MAX_NESTING - 2));
+            assert_eq!(result, Ok(Some("1".to_string())), "{:?}", form);
+
+            let errors = run_with_main_stack(nested(form, DEFAULT_MAX_NESTING - 1))
+                .expect_err("Should be too deep");
+            assert!(
+                errors[0].ends_with("Expression nesting too deep."),
+                "{:?}",
+                errors
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolver_error_is_attributed_to_resolve_phase() {
+        let (phases, diagnostics) = error::collect_diagnostics(|| {
+            let tokens = Scanner::new("return 1;".to_string()).scan_tokens();
+            let mut statements = Parser::new(tokens).parse().expect("Should parse");
+            statements.resolve(&mut Resolver::new());
+            [Phase::Scan, Phase::Parse, Phase::Resolve].map(error::had_error_in)
+        });
+
+        assert_eq!(phases, [false, false, true]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].phase, Phase::Resolve);
+        assert!(!error::had_error());
+    }
+
+    #[test]
+    fn test_resolve_returns_its_diagnostics() {
+        let (statements, _) = parse("fun f() { var a = 1; var a = 2; }\nreturn 3;");
+        let mut statements = statements.expect("Should parse");
+
+        let diagnostics = resolve(&mut statements);
+
+        let messages: Vec<&str> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Already a variable with this name in this scope.",
+                "Can't return from top-level code."
+            ]
+        );
+        assert!(!error::had_error());
+    }
+
+    #[test]
+    fn test_stages_can_be_run_separately() {
+        let mut interpreter = Interpreter::default();
+
+        let (statements, _) = parse("var count = 0; fun next() { count += 1; return count; }");
+        let mut declarations = statements.expect("Should parse");
+        assert!(resolve(&mut declarations).is_empty());
+        assert!(evaluate(&mut interpreter, &declarations).is_ok());
+
+        // Later sources can be resolved and evaluated on their own against the same globals
+        let (statements, _) = parse("next(); next();");
+        let mut calls = statements.expect("Should parse");
+        assert!(resolve(&mut calls).is_empty());
+        let last = evaluate(&mut interpreter, &calls)
+            .expect("Should run")
+            .map(|value| value.stringify());
+        assert_eq!(last.as_deref(), Some("2"));
+
+        // Evaluating the same statements again doesn't need them parsed or resolved again
+        let last = evaluate(&mut interpreter, &calls)
+            .expect("Should run")
+            .map(|value| value.stringify());
+        assert_eq!(last.as_deref(), Some("4"));
+    }
+
+    #[test]
+    fn test_runtime_error_does_not_affect_the_next_line() {
+        let mut interpreter = Interpreter::default();
+        let mut run = |source: &str| {
+            let (statements, _) = parse(source);
+            let mut statements = statements.expect("Should parse");
+            assert!(resolve(&mut statements).is_empty());
+            evaluate(&mut interpreter, &statements).map(|value| value.map(|v| v.stringify()))
+        };
+
+        assert!(run("var total = 10; fun split(n) { var part = total / n; return part; }").is_ok());
+
+        // Fails within a function and a block, after defining a global
+        let err = run("var before = 1; { var local = 2; split(0); }").expect_err("Should fail");
+        assert_eq!(err.message, "Cannot divide by 0.");
+        assert!(error::had_runtime_error());
+
+        // Like the REPL does before the next line
+        error::reset_errors();
+        assert!(!error::had_runtime_error());
+
+        let last = run("before + split(2);").expect("Should run");
+        assert_eq!(last.as_deref(), Some("6"));
+        assert!(!error::had_runtime_error());
+    }
+
+    fn run_errors(source: &str) -> Vec<LoxError> {
+        run(&mut Interpreter::default(), source).expect_err("Should fail")
+    }
+
+    #[test]
+    fn test_run_returns_the_errors_of_each_phase() {
+        let errors = run_errors("var a = 1;\nvar b = @;");
+        assert!(
+            matches!(&errors[0], LoxError::Scan { line: 2, message } if message == "Unexpected character: '@'.")
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "[line 2] Error: Unexpected character: '@'."
+        );
+
+        let errors = run_errors("print (1;");
+        let LoxError::Parse { token, message } = &errors[0] else {
+            panic!("Expected a parse error, got {:?}", errors);
+        };
+        assert_eq!(
+            (token.lexeme.as_str(), message.as_str()),
+            (";", "Expect ')' after expression.")
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "[line 1] Error at ';': Expect ')' after expression."
+        );
+
+        let errors = run_errors("{ var a = 1; var a = 2; } return 3;");
+        assert_eq!(errors.len(), 2);
+        assert!(errors
+            .iter()
+            .all(|error| matches!(error, LoxError::Resolve { .. })));
+        assert_eq!(
+            errors[1].to_string(),
+            "[line 1] Error at 'return': Can't return from top-level code."
+        );
+
+        let errors = run_errors("var a = 1;\na + nil;");
+        assert!(matches!(&errors[..], [LoxError::Runtime(error)] if error.token.line == 2));
+        assert_eq!(
+            errors[0].to_string(),
+            "[line 2]: Operands must be two numbers or two strings."
+        );
+        assert!(!error::had_runtime_error());
+    }
+
+    #[test]
+    fn test_run_keeps_the_globals_and_ignores_warnings() {
+        let mut interpreter = Interpreter::default();
+        assert!(run(&mut interpreter, "var a = 1; { var b = a; { var b = 2; } }").is_ok());
+
+        let last = run(&mut interpreter, "a + 1;").expect("Should run");
+        assert_eq!(last.map(|value| value.stringify()).as_deref(), Some("2"));
+    }
+
+    fn run_in_mode(config: InterpreterConfig, source: &str) -> Option<String> {
+        let mut interpreter = Interpreter::new_with_config(config);
+        let last = run(&mut interpreter, source).expect("Should run");
+        last.map(|value| value.stringify())
+    }
+
+    #[test]
+    fn test_run_allows_factory_initializers() {
+        let config = InterpreterConfig {
+            factory_initializers: true,
+            ..Default::default()
+        };
+        let source = "class B {} class A { init() { return B(); } } A();";
+
+        assert_eq!(run_in_mode(config, source).as_deref(), Some("B instance"));
+    }
+
+    #[test]
+    fn test_run_allows_implicit_returns() {
+        let config = InterpreterConfig {
+            implicit_returns: true,
+            ..Default::default()
+        };
+
+        let last = run_in_mode(config, "fun f() { 1 + 1 } f();");
+        assert_eq!(last.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn test_run_allows_global_this() {
+        let config = InterpreterConfig {
+            global_this: true,
+            ..Default::default()
+        };
+
+        let last = run_in_mode(config, "this.answer = 42; this.answer;");
+        assert_eq!(last.as_deref(), Some("42"));
+    }
+
+    #[test]
+    fn test_run_allows_redefining_print() {
+        let config = InterpreterConfig {
+            print_function: true,
+            ..Default::default()
+        };
+
+        let last = run_in_mode(config, "var print = \"redefined\"; print;");
+        assert_eq!(last.as_deref(), Some("redefined"));
+    }
+}
@@ -2,24 +2,39 @@ use std::{cell::RefCell, collections::VecDeque, fmt::Debug, rc::Rc};
 
 use crate::{
     environment::Environment,
-    interpreter::{Evaluatable, LoxValue, RuntimeError, RuntimeEvent, Stringifyable},
+    interpreter::{Evaluatable, Interpreter, LoxValue, RuntimeError, RuntimeEvent, Stringifyable},
     lox_class::LoxClass,
     lox_instance::LoxInstance,
     stmt::Stmt,
+    symbol::Symbol,
     token::Token,
     token_type::TokenType,
 };
 
 #[derive(Debug)]
 pub struct FunctionStmt {
-    pub name: Rc<Token>,
+    // Anonymous functions don't have a name
+    pub name: Option<Rc<Token>>,
     pub params: Rc<Vec<Token>>,
     pub body: Rc<RefCell<Vec<Stmt>>>,
 }
 
+// Signature of functions implemented in Rust, the arguments are already checked against the arity
+pub type NativeFn =
+    fn(&Interpreter, Vec<Rc<LoxValue>>, &Token) -> Result<Rc<LoxValue>, RuntimeEvent>;
+
 #[derive(Debug)]
 pub enum LoxCallable {
-    ClockFunction,
+    Native {
+        name: Symbol,
+        // For variadic natives the minimum number of arguments
+        arity: usize,
+        variadic: bool,
+        function: NativeFn,
+        // For methods of built-in values like numbers the value they were looked up on, which
+        // is passed as the first argument (and doesn't count towards the arity)
+        receiver: Option<Rc<LoxValue>>,
+    },
     Function {
         declaration: Rc<FunctionStmt>,
         closure: Rc<RefCell<Environment>>,
@@ -45,7 +60,7 @@ impl LoxCallable {
 
     pub fn arity(&self) -> usize {
         match self {
-            LoxCallable::ClockFunction => 0,
+            LoxCallable::Native { arity, .. } => *arity,
             LoxCallable::Function {
                 declaration,
                 closure: _,
@@ -55,16 +70,53 @@ impl LoxCallable {
         }
     }
 
+    // How the callable shows up in the profile
+    fn profile_name(&self) -> String {
+        match self {
+            LoxCallable::Native { name, .. } => name.to_string(),
+            LoxCallable::Function { declaration, .. } => match &declaration.name {
+                Some(name) => name.lexeme.to_string(),
+                None => "<anonymous fn>".to_string(),
+            },
+            LoxCallable::Class { class } => class.name.to_string(),
+        }
+    }
+
     pub fn call(
         &self,
+        interpreter: &Interpreter,
+        arguments: VecDeque<Rc<LoxValue>>,
+        call_token: &Token,
+    ) -> Result<Rc<LoxValue>, RuntimeEvent> {
+        match interpreter.profiler() {
+            Some(profiler) => {
+                profiler.borrow_mut().enter(self.profile_name());
+                let result = self.call_unprofiled(interpreter, arguments, call_token);
+                profiler.borrow_mut().exit();
+                result
+            }
+            None => self.call_unprofiled(interpreter, arguments, call_token),
+        }
+    }
+
+    fn call_unprofiled(
+        &self,
+        interpreter: &Interpreter,
         mut arguments: VecDeque<Rc<LoxValue>>,
         call_token: &Token,
     ) -> Result<Rc<LoxValue>, RuntimeEvent> {
-        if self.arity() != arguments.len() {
+        let variadic = matches!(self, LoxCallable::Native { variadic: true, .. });
+        let accepted = match variadic {
+            true => arguments.len() >= self.arity(),
+            false => arguments.len() == self.arity(),
+        };
+
+        if !accepted {
             return Err(RuntimeEvent::Error(RuntimeError::new(
                 call_token.to_owned(),
                 format!(
-                    "Expected {} arguments but got {}.",
+                    "Expected {}{} arguments but got {}.",
+                    if variadic { "at least " } else { "" },
                     self.arity(),
                     arguments.len()
                 ),
@@ -72,12 +124,13 @@ impl LoxCallable {
         }
 
         match self {
-            LoxCallable::ClockFunction => {
-                let now = std::time::SystemTime::now();
-                let duration = now
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .expect("Time went backwards");
-                Ok(Rc::new(LoxValue::Number(duration.as_secs_f64())))
+            LoxCallable::Native {
+                function, receiver, ..
+            } => {
+                if let Some(receiver) = receiver {
+                    arguments.push_front(receiver.clone());
+                }
+                function(interpreter, Vec::from(arguments), call_token)
             }
             LoxCallable::Function {
                 declaration,
@@ -88,7 +141,7 @@ impl LoxCallable {
 
                 for param in declaration.params.iter() {
                     function_env.define(
-                        param.lexeme.to_owned(),
+                        param.lexeme.symbol(),
                         arguments
                             .pop_front()
                             .expect("Argument list was checked with arity of the function"),
@@ -98,28 +151,27 @@ impl LoxCallable {
                 let result = declaration
                     .body
                     .borrow()
-                    .evaluate(Rc::new(RefCell::new(function_env)));
+                    .evaluate(interpreter, Rc::new(RefCell::new(function_env)));
 
                 match result {
                     Ok(_) => match is_initializer {
                         // init() methods should always return this
                         true => closure.borrow().get_at(
                             Some(0),
-                            &Token::new(TokenType::This, "this".to_string(), call_token.line),
+                            &Token::new(TokenType::This, Symbol::THIS, call_token.line),
                         ),
                         false => Ok(Rc::new(LoxValue::Nil)),
                     },
                     Err(err) => match err {
-                        RuntimeEvent::Return(value) => {
+                        RuntimeEvent::Return { keyword, value } => {
                             match is_initializer {
+                                true if interpreter.config().factory_initializers => {
+                                    factory_result(closure, keyword, value)
+                                }
                                 // Handle case where have an early return in an initializer function
                                 true => closure.borrow().get_at(
                                     Some(0),
-                                    &Token::new(
-                                        TokenType::This,
-                                        "this".to_string(),
-                                        call_token.line,
-                                    ),
+                                    &Token::new(TokenType::This, Symbol::THIS, call_token.line),
                                 ),
                                 false => Ok(value),
                             }
@@ -132,12 +184,12 @@ impl LoxCallable {
                 let instance = LoxInstance::new(class.clone());
                 let instance = Rc::new(RefCell::new(instance));
 
-                if let Some(initializer) = class.find_method("init") {
+                if let Some(initializer) = class.find_method(Symbol::INIT) {
                     // First bind the init method to the instance (so it has access to `this`)
                     match initializer.bind(instance.clone()).as_ref() {
                         LoxValue::Callable(callable) => {
-                            // And then invoke it
-                            callable.call(arguments, call_token)?;
+                            // And then invoke it, which gives `this` unless it's a factory
+                            return callable.call(interpreter, arguments, call_token);
                         }
                         _ => unreachable!("Bind always returns a callable"),
                     };
@@ -149,15 +201,38 @@ impl LoxCallable {
     }
 }
 
+// What an initializer returning a value gives in factory mode: an instance is the result
+// of calling the class, while nil still gives `this`
+fn factory_result(
+    closure: &Rc<RefCell<Environment>>,
+    keyword: Token,
+    value: Rc<LoxValue>,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    match value.as_ref() {
+        LoxValue::Instance(_) => Ok(value),
+        LoxValue::Nil => closure.borrow().get_at(
+            Some(0),
+            &Token::new(TokenType::This, Symbol::THIS, keyword.line),
+        ),
+        _ => Err(RuntimeEvent::Error(RuntimeError::new(
+            keyword,
+            "An initializer can only return an instance.".to_string(),
+        ))),
+    }
+}
+
 impl Stringifyable for LoxCallable {
     fn stringify(&self) -> String {
         match self {
-            LoxCallable::ClockFunction => "<native fn>".to_string(),
+            LoxCallable::Native { .. } => "<native fn>".to_string(),
             LoxCallable::Function {
                 declaration,
                 closure: _,
                 is_initializer: _,
-            } => format!("<fn {}>", declaration.name.lexeme),
+            } => match &declaration.name {
+                Some(name) => format!("<fn {}>", name.lexeme),
+                None => "<anonymous fn>".to_string(),
+            },
             LoxCallable::Class { class } => class.name.to_string(),
         }
     }
@@ -1,29 +1,61 @@
 use std::{collections::HashMap, rc::Rc};
 
-use crate::{interpreter::LoxValue, lox_callable::LoxCallable};
+use crate::{interpreter::LoxValue, lox_callable::LoxCallable, symbol::Symbol};
 
 #[derive(Debug)]
 pub struct LoxClass {
-    pub name: String,
+    pub name: Symbol,
+    // Fixed by the declaration, so `super` in the methods always refers to the same class
     superclass: Option<Rc<LoxValue>>,
-    methods: HashMap<String, Rc<LoxValue>>,
+    methods: HashMap<Symbol, Rc<LoxValue>>,
+    // Names of the own methods in declaration order
+    method_names: Vec<Symbol>,
 }
 
 impl LoxClass {
+    // A method declared more than once keeps its first position, but the last declaration wins
     pub fn new(
-        name: String,
+        name: Symbol,
         superclass: Option<Rc<LoxValue>>,
-        methods: HashMap<String, Rc<LoxValue>>,
+        methods: Vec<(Symbol, Rc<LoxValue>)>,
     ) -> LoxClass {
+        let mut method_names = vec![];
+        let mut method_map = HashMap::new();
+        for (method_name, method) in methods {
+            if method_map.insert(method_name, method).is_none() {
+                method_names.push(method_name);
+            }
+        }
+
         LoxClass {
             name,
             superclass,
-            methods,
+            methods: method_map,
+            method_names,
+        }
+    }
+
+    pub fn method_names(&self) -> &[Symbol] {
+        &self.method_names
+    }
+
+    pub fn superclass(&self) -> Option<&Rc<LoxClass>> {
+        match self.superclass.as_deref()? {
+            LoxValue::Callable(LoxCallable::Class { class }) => Some(class),
+            _ => panic!("Superclass must be a LoxClass"),
         }
     }
 
-    pub fn find_method(&self, name: &str) -> Option<Rc<LoxValue>> {
-        match self.methods.get(name).map(Rc::clone) {
+    // Whether this is the given class or inherits from it
+    pub fn is_subclass_of(&self, other: &LoxClass) -> bool {
+        std::ptr::eq(self, other)
+            || self
+                .superclass()
+                .is_some_and(|superclass| superclass.is_subclass_of(other))
+    }
+
+    pub fn find_method(&self, name: Symbol) -> Option<Rc<LoxValue>> {
+        match self.methods.get(&name).map(Rc::clone) {
             None => {
                 if let Some(superclass) = &self.superclass {
                     return match superclass.as_ref() {
@@ -38,7 +70,7 @@ impl LoxClass {
     }
 
     pub fn arity(&self) -> usize {
-        match self.find_method("init") {
+        match self.find_method(Symbol::INIT) {
             Some(initializer) => match initializer.as_ref() {
                 LoxValue::Callable(callable) => callable.arity(),
                 _ => unreachable!("All class methods must be functions"),
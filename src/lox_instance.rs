@@ -5,18 +5,26 @@ use crate::{
     interpreter::{LoxValue, RuntimeError, RuntimeEvent, Stringifyable},
     lox_callable::LoxCallable,
     lox_class::LoxClass,
+    symbol::Symbol,
     token::Token,
 };
 
 #[derive(Debug)]
 pub struct LoxInstance {
     klass: Rc<LoxClass>,
-    fields: HashMap<String, Rc<LoxValue>>,
+    fields: HashMap<Symbol, Rc<LoxValue>>,
+    // Printed instead of "<class> instance", e.g. `Color.RED` for an enum variant
+    label: Option<String>,
+    // Fields can't be set or deleted, e.g. for enums and their variants
+    read_only: bool,
 }
 
 impl Stringifyable for LoxInstance {
     fn stringify(&self) -> String {
-        format!("{} instance", self.klass.name)
+        match &self.label {
+            Some(label) => label.clone(),
+            None => format!("{} instance", self.klass.name),
+        }
     }
 }
 
@@ -25,21 +33,39 @@ impl LoxInstance {
         LoxInstance {
             klass,
             fields: HashMap::new(),
+            label: None,
+            read_only: false,
         }
     }
 
-    pub fn get(this: Rc<RefCell<LoxInstance>>, name: &Token) -> Result<Rc<LoxValue>, RuntimeEvent> {
-        if this.borrow().fields.contains_key(&name.lexeme) {
-            return Ok(this
-                .borrow()
-                .fields
-                .get(&name.lexeme)
-                .expect("Value must be present, key was checked")
-                .clone());
+    // An instance whose fields are fixed, printed as the label
+    pub fn read_only(
+        klass: Rc<LoxClass>,
+        label: String,
+        fields: HashMap<Symbol, Rc<LoxValue>>,
+    ) -> LoxInstance {
+        LoxInstance {
+            klass,
+            fields,
+            label: Some(label),
+            read_only: true,
         }
+    }
+
+    pub fn get(this: Rc<RefCell<LoxInstance>>, name: &Token) -> Result<Rc<LoxValue>, RuntimeEvent> {
+        // The borrow only covers the lookup, binding the method must not overlap with it
+        let method = {
+            let instance = this.try_borrow().map_err(|_| in_use_error(name))?;
 
-        if let Some(method) = this.borrow().klass.find_method(&name.lexeme) {
-            return Ok(method.bind(this.clone()));
+            if let Some(value) = instance.fields.get(&name.lexeme.symbol()) {
+                return Ok(value.clone());
+            }
+
+            instance.klass.find_method(name.lexeme.symbol())
+        };
+
+        if let Some(method) = method {
+            return Ok(method.bind(this));
         }
 
         Err(RuntimeEvent::Error(RuntimeError {
@@ -48,10 +74,65 @@ impl LoxInstance {
         }))
     }
 
-    pub fn set(&mut self, name: &Token, value: Rc<LoxValue>) -> Result<(), RuntimeEvent> {
-        self.fields.insert(name.lexeme.to_string(), value);
+    pub fn class(&self) -> &Rc<LoxClass> {
+        &self.klass
+    }
+
+    pub fn is_instance_of(&self, class: &LoxClass) -> bool {
+        self.klass.is_subclass_of(class)
+    }
+
+    // The method of the instance's class bound to it, fields are not considered
+    pub fn bound_method(this: &Rc<RefCell<LoxInstance>>, name: Symbol) -> Option<Rc<LoxValue>> {
+        let method = this.try_borrow().ok()?.klass.find_method(name)?;
+        Some(method.bind(this.clone()))
+    }
+
+    pub fn set(
+        this: &Rc<RefCell<LoxInstance>>,
+        name: &Token,
+        value: Rc<LoxValue>,
+    ) -> Result<(), RuntimeEvent> {
+        let mut instance = this.try_borrow_mut().map_err(|_| in_use_error(name))?;
+        instance.check_writable(name)?;
+        instance.fields.insert(name.lexeme.symbol(), value);
         Ok(())
     }
+
+    // Removes a field, returning whether it existed. Methods belong to the class and stay,
+    // so deleting a name that's only a method does nothing.
+    pub fn delete(this: &Rc<RefCell<LoxInstance>>, name: &Token) -> Result<bool, RuntimeEvent> {
+        let mut instance = this.try_borrow_mut().map_err(|_| in_use_error(name))?;
+        instance.check_writable(name)?;
+        Ok(instance.fields.remove(&name.lexeme.symbol()).is_some())
+    }
+
+    fn check_writable(&self, name: &Token) -> Result<(), RuntimeEvent> {
+        if !self.read_only {
+            return Ok(());
+        }
+
+        Err(RuntimeEvent::Error(RuntimeError {
+            token: name.clone(),
+            message: format!(
+                "Can't change property '{}' of read-only {}.",
+                name.lexeme,
+                self.stringify()
+            ),
+        }))
+    }
+}
+
+// Evaluation never holds on to an instance borrow while running Lox code, so this is only a
+// safety net that turns a conflicting access into a runtime error instead of a panic
+fn in_use_error(name: &Token) -> RuntimeEvent {
+    RuntimeEvent::Error(RuntimeError {
+        token: name.clone(),
+        message: format!(
+            "Can't access property '{}' while the instance is in use.",
+            name.lexeme
+        ),
+    })
 }
 
 impl LoxValue {
@@ -64,7 +145,7 @@ impl LoxValue {
                     is_initializer,
                 } => {
                     let mut environment = Environment::new_enclosing(closure.clone());
-                    environment.define("this".to_string(), Rc::new(LoxValue::Instance(instance)));
+                    environment.define(Symbol::THIS, Rc::new(LoxValue::Instance(instance)));
                     Rc::new(LoxValue::Callable(LoxCallable::new_function(
                         declaration.clone(),
                         Rc::new(RefCell::new(environment)),
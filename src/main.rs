@@ -1,35 +1,79 @@
-use std::{env, io::Write};
-
-use interpreter::Interpreter;
-use parser::Parser;
-use resolver::{Resolvable, Resolver};
-use scanner::Scanner;
-
-mod ast_printer;
-mod environment;
-mod error;
-mod expr;
-mod interpreter;
-mod lox_callable;
-mod lox_class;
-mod lox_instance;
-mod parser;
-mod resolver;
-mod scanner;
-mod stmt;
-mod token;
-mod token_type;
+use std::{
+    env,
+    fs::File,
+    io::{BufReader, Write},
+    rc::Rc,
+};
+
+use rlox::{
+    compile, constant_folding,
+    error::{self, Diagnostic},
+    interpreter::{Interpreter, InterpreterConfig, LoxValue, RuntimeError, Stringifyable},
+    scanner::Scanner,
+};
 
 const DEBUG_MEMORY: bool = false;
 
+const USAGE: &str = "Usage: rlox [--loose] [--strict-booleans] [--profile] [--no-shadow-warnings] [--print-last] [--print-function] [--strict-uninitialized] [--check] [--global-this] [--nil-undefined-globals] [--factory-init] [--implicit-returns] [--sandbox] [script]";
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let (flags, args): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|arg| arg.starts_with("--"));
 
-    if args.len() > 2 {
-        println!("Usage: rlox [script]");
+    if args.len() > 1 {
+        println!("{}", USAGE);
         std::process::exit(1);
     }
 
+    let mut config = InterpreterConfig {
+        instant_sleep: env::var_os("RLOX_INSTANT_SLEEP").is_some(),
+        filesystem: true,
+        process_exit: true,
+        ..Default::default()
+    };
+    let mut warn_shadowing = true;
+    let mut print_last = false;
+    let mut check = false;
+    for flag in flags {
+        match flag.as_str() {
+            "--loose" => config.loose_coercion = true,
+            "--strict-booleans" => config.strict_booleans = true,
+            "--profile" => config.profile = true,
+            "--no-shadow-warnings" => warn_shadowing = false,
+            "--print-last" => print_last = true,
+            "--check" => check = true,
+            "--global-this" => config.global_this = true,
+            "--nil-undefined-globals" => config.nil_for_undefined_globals = true,
+            "--factory-init" => config.factory_initializers = true,
+            "--implicit-returns" => config.implicit_returns = true,
+            // Leaves out the natives that reach outside of the interpreter
+            "--sandbox" => {
+                config.filesystem = false;
+                config.process_exit = false;
+            }
+            "--print-function" => config.print_function = true,
+            "--strict-uninitialized" => config.strict_uninitialized = true,
+            _ => {
+                println!("Unknown option '{}'.\n{}", flag, USAGE);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // The value printed doesn't need a `;` after it
+    config.optional_last_semicolon = print_last;
+
+    if check {
+        match args.first() {
+            Some(path) => check_file(path, &config, warn_shadowing),
+            None => {
+                println!("--check needs a script.\n{}", USAGE);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     if DEBUG_MEMORY {
         // Wait until user presses any button
         println!("Press any key to start the program execution");
@@ -37,12 +81,26 @@ fn main() {
     }
 
     {
-        let mut interpreter = Interpreter::new();
+        let mut interpreter = Interpreter::new_with_config(config);
+
+        // Pin `clock()` to a fixed value, so the output of time dependent scripts can be asserted
+        if let Ok(fixed_clock) = env::var("RLOX_FIXED_CLOCK") {
+            let fixed_clock: f64 = fixed_clock
+                .parse()
+                .expect("RLOX_FIXED_CLOCK must be a number");
+            interpreter.set_clock(move || fixed_clock);
+        }
+        // Same for `random()` and `randInt()`
+        if let Ok(seed) = env::var("RLOX_RANDOM_SEED") {
+            let seed: u64 = seed.parse().expect("RLOX_RANDOM_SEED must be an integer");
+            interpreter.seed_random(seed);
+        }
 
-        if args.len() == 2 {
-            run_file(&args[1], &mut interpreter);
+        if let Some(path) = args.first() {
+            run_file(path, &mut interpreter, warn_shadowing, print_last);
         } else {
-            run_prompt(&mut interpreter);
+            run_prompt(&mut interpreter, warn_shadowing);
+            print_profile(&interpreter);
         }
     }
 
@@ -54,20 +112,67 @@ fn main() {
     }
 }
 
-fn run_file(path: &str, interpreter: &mut Interpreter) {
+fn run_file(path: &str, interpreter: &mut Interpreter, warn_shadowing: bool, print_last: bool) {
+    let file = File::open(path).expect("Failed to read file");
+
+    // Scanned while it's read, the script's text is only read as a whole to underline diagnostics
+    let result = run(
+        Scanner::from_reader(BufReader::new(file)),
+        || std::fs::read_to_string(path).expect("Failed to read file"),
+        interpreter,
+        warn_shadowing,
+        true,
+    );
+    if let (true, Some(Ok(Some(value)))) = (print_last, &result) {
+        println!("{}", value.stringify());
+    }
+    print_profile(interpreter);
+
+    match result {
+        None => std::process::exit(65),
+        Some(Err(_)) => std::process::exit(70),
+        Some(Ok(_)) => {}
+    }
+}
+
+// Reports the diagnostics of everything before running the script, without running it.
+// Warnings fail the check as well, so it can be used as a linter.
+fn check_file(path: &str, config: &InterpreterConfig, warn_shadowing: bool) {
     let source = std::fs::read_to_string(path).expect("Failed to read file");
 
-    run(source, interpreter);
+    let (_, diagnostics) = error::collect_diagnostics(|| {
+        compile(Scanner::new(source.clone()), config, warn_shadowing)
+    });
+    report(&diagnostics, || source);
 
-    if error::had_error() {
+    if !diagnostics.is_empty() {
         std::process::exit(65);
     }
-    if error::had_runtime_error() {
-        std::process::exit(70);
+}
+
+// Prints the diagnostics, each followed by its line with the token underlined. The source is only
+// asked for if there are any, so a script that compiles doesn't have to be read twice.
+fn report(diagnostics: &[Diagnostic], source: impl FnOnce() -> String) {
+    if diagnostics.is_empty() {
+        return;
+    }
+
+    let source = source();
+    for diagnostic in diagnostics {
+        eprintln!("{}", diagnostic);
+        if let Some(underline) = diagnostic.underline(&source) {
+            eprintln!("{}", underline);
+        }
     }
 }
 
-fn run_prompt(interpreter: &mut Interpreter) {
+fn print_profile(interpreter: &Interpreter) {
+    if let Some(report) = interpreter.profile_report() {
+        eprint!("{}", report);
+    }
+}
+
+fn run_prompt(interpreter: &mut Interpreter, warn_shadowing: bool) {
     loop {
         print!("> ");
         std::io::stdout().flush().expect("Cannot flush stdout");
@@ -81,31 +186,45 @@ fn run_prompt(interpreter: &mut Interpreter) {
             break;
         }
 
-        run(input, interpreter);
+        if input.trim() == ":reset" {
+            interpreter.reset();
+            continue;
+        }
+
+        // Errors are reported already, the next line can go on with the globals defined so far
+        let _ = run(
+            Scanner::new(input.clone()),
+            || input,
+            interpreter,
+            warn_shadowing,
+            false,
+        );
         std::io::stdout().flush().expect("Cannot flush stdout");
 
-        error::set_had_error(false);
+        error::reset_errors();
     }
 }
 
-fn run(source: String, interpreter: &mut Interpreter) {
-    let tokens = Scanner::new(source).scan_tokens();
-    let expr = Parser::new(tokens).parse();
-
-    // Check if we had error during parsing
-    if error::had_error() {
-        return;
-    }
-
-    let mut statements = expr.expect("Should have expression as there was no error reported");
-
-    let mut resolver = Resolver::new();
-    statements.resolve(&mut resolver);
-
-    // Check again after resolution
-    if error::had_error() {
-        return;
+// Returns the value of the last expression statement, see `Interpreter::interpret`. Nothing
+// runs if the source doesn't compile, then None is returned. The diagnostics are printed with
+// `report`, which reads the source with `source` for their underlines.
+// Constants are only folded for whole programs, see `constant_folding::fold_constants`.
+fn run(
+    scanner: Scanner,
+    source: impl FnOnce() -> String,
+    interpreter: &mut Interpreter,
+    warn_shadowing: bool,
+    whole_program: bool,
+) -> Option<Result<Option<Rc<LoxValue>>, RuntimeError>> {
+    let config = interpreter.config();
+    let (statements, diagnostics) =
+        error::collect_diagnostics(|| compile(scanner, config, warn_shadowing));
+    report(&diagnostics, source);
+
+    let mut statements = statements?;
+    // Fields of the global object act like globals, and the profile should list every call
+    if whole_program && !config.global_this && !config.profile {
+        constant_folding::fold_constants(&mut statements);
     }
-
-    interpreter.interpret(statements);
+    Some(interpreter.interpret(&statements))
 }
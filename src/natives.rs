@@ -0,0 +1,139 @@
+use std::{rc::Rc, time::Duration};
+
+mod compare;
+mod math;
+mod number;
+mod random;
+mod reflection;
+mod string;
+mod system;
+
+pub(crate) use math::pure_function;
+pub(crate) use random::Rng;
+pub(crate) use string::characters;
+
+use crate::{
+    environment::Environment,
+    interpreter::{Interpreter, InterpreterConfig, LoxValue, RuntimeError, RuntimeEvent},
+    lox_callable::{LoxCallable, NativeFn},
+    symbol::Symbol,
+    token::Token,
+};
+
+pub fn define_natives(environment: &mut Environment, config: &InterpreterConfig) {
+    define(environment, "clock", 0, clock);
+    define(environment, "sleep", 1, sleep);
+    // Shadowed by the print statement, unless `print` isn't scanned as a keyword
+    define(environment, "print", 1, print);
+
+    compare::define_natives(environment);
+    math::define_natives(environment);
+    random::define_natives(environment);
+    reflection::define_natives(environment);
+    string::define_natives(environment);
+    system::define_natives(environment, config);
+}
+
+fn define(environment: &mut Environment, name: &str, arity: usize, function: NativeFn) {
+    define_native(environment, name, arity, false, function);
+}
+
+// Defines a native taking `min_arity` or more arguments
+fn define_variadic(
+    environment: &mut Environment,
+    name: &str,
+    min_arity: usize,
+    function: NativeFn,
+) {
+    define_native(environment, name, min_arity, true, function);
+}
+
+fn define_native(
+    environment: &mut Environment,
+    name: &str,
+    arity: usize,
+    variadic: bool,
+    function: NativeFn,
+) {
+    let name = Symbol::intern(name);
+    environment.define(
+        name,
+        Rc::new(LoxValue::Callable(LoxCallable::Native {
+            name,
+            arity,
+            variadic,
+            function,
+            receiver: None,
+        })),
+    );
+}
+
+// Looks up a method of a built-in value, bound to that value
+pub fn method(receiver: &Rc<LoxValue>, name: Symbol) -> Option<Rc<LoxValue>> {
+    let (arity, function) = match receiver.as_ref() {
+        LoxValue::Number(_) => number::method(name)?,
+        _ => return None,
+    };
+
+    Some(Rc::new(LoxValue::Callable(LoxCallable::Native {
+        name,
+        arity,
+        variadic: false,
+        function,
+        receiver: Some(receiver.clone()),
+    })))
+}
+
+fn print(
+    interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    _call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    interpreter.print(&arguments[0]);
+    Ok(Rc::new(LoxValue::Nil))
+}
+
+fn error(call_token: &Token, message: &str) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    Err(RuntimeEvent::Error(RuntimeError::new(
+        call_token.clone(),
+        message.to_string(),
+    )))
+}
+
+fn clock(
+    interpreter: &Interpreter,
+    _arguments: Vec<Rc<LoxValue>>,
+    _call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    Ok(Rc::new(LoxValue::Number(interpreter.now())))
+}
+
+// Blocks for the given amount of milliseconds
+fn sleep(
+    interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    let milliseconds = match arguments[0].as_ref() {
+        LoxValue::Number(milliseconds) if milliseconds.is_finite() && *milliseconds >= 0.0 => {
+            *milliseconds
+        }
+        _ => {
+            return error(
+                call_token,
+                "sleep() expects a non-negative number of milliseconds.",
+            )
+        }
+    };
+
+    // Checked even when sleeping instantly, so scripts behave the same in tests
+    let Ok(duration) = Duration::try_from_secs_f64(milliseconds / 1000.0) else {
+        return error(call_token, "sleep() can't wait that long.");
+    };
+
+    if !interpreter.config().instant_sleep {
+        std::thread::sleep(duration);
+    }
+
+    Ok(Rc::new(LoxValue::Nil))
+}
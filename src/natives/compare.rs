@@ -0,0 +1,101 @@
+use std::{cell::RefCell, cmp::Ordering, rc::Rc};
+
+use crate::{
+    environment::Environment,
+    interpreter::{Interpreter, LoxValue, RuntimeEvent, Stringifyable},
+    token::Token,
+};
+
+use super::define;
+
+pub fn define_natives(environment: &mut Environment) {
+    define(environment, "compare", 2, compare);
+}
+
+// -1, 0 or 1 as `a` sorts before, with or after `b`, for sorting values of any type unlike `<`.
+// Values of different types sort by type: nil, booleans (false first), numbers, strings (by
+// code point), lists (element by element, shorter first), maps (entry by entry in insertion
+// order, key before value), functions and classes, then instances. NaN sorts after all other
+// numbers. Functions, classes and instances sort by how they print, so only those that print the
+// same, like two instances of a class, compare as 0 without being equal.
+fn compare(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    _call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    let ordering = total_order(&arguments[0], &arguments[1], &mut vec![]);
+    Ok(LoxValue::number(ordering as i8 as f64))
+}
+
+// Like for equality, lists and maps already being compared further up count as the same, so
+// comparing cyclic ones terminates
+fn total_order(
+    a: &Rc<LoxValue>,
+    b: &Rc<LoxValue>,
+    comparing: &mut Vec<(*const (), *const ())>,
+) -> Ordering {
+    match (a.as_ref(), b.as_ref()) {
+        (LoxValue::Boolean(a), LoxValue::Boolean(b)) => a.cmp(b),
+        (LoxValue::Number(a), LoxValue::Number(b)) => match (a.is_nan(), b.is_nan()) {
+            (false, false) => a.partial_cmp(b).expect("Only NaN is unordered"),
+            (nan_a, nan_b) => nan_a.cmp(&nan_b),
+        },
+        (LoxValue::String(a), LoxValue::String(b)) => a.cmp(b),
+        (LoxValue::List(a), LoxValue::List(b)) => {
+            sequences_order(a, b, comparing, |a, b, comparing| {
+                total_order(a, b, comparing)
+            })
+        }
+        (LoxValue::Map(a), LoxValue::Map(b)) => sequences_order(
+            a,
+            b,
+            comparing,
+            |(a_key, a_value), (b_key, b_value), comparing| {
+                total_order(a_key, b_key, comparing)
+                    .then_with(|| total_order(a_value, b_value, comparing))
+            },
+        ),
+        (LoxValue::Callable(a), LoxValue::Callable(b)) => a.stringify().cmp(&b.stringify()),
+        (LoxValue::Instance(a), LoxValue::Instance(b)) => {
+            a.borrow().stringify().cmp(&b.borrow().stringify())
+        }
+        _ => type_rank(a).cmp(&type_rank(b)),
+    }
+}
+
+// Lists and maps in order of their first differing element, or of their length
+fn sequences_order<T>(
+    a: &Rc<RefCell<Vec<T>>>,
+    b: &Rc<RefCell<Vec<T>>>,
+    comparing: &mut Vec<(*const (), *const ())>,
+    element_order: impl Fn(&T, &T, &mut Vec<(*const (), *const ())>) -> Ordering,
+) -> Ordering {
+    let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+    if Rc::ptr_eq(a, b) || comparing.contains(&pair) {
+        return Ordering::Equal;
+    }
+
+    comparing.push(pair);
+    let (a, b) = (a.borrow(), b.borrow());
+    let ordering = a
+        .iter()
+        .zip(b.iter())
+        .map(|(a, b)| element_order(a, b, comparing))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()));
+    comparing.pop();
+    ordering
+}
+
+fn type_rank(value: &LoxValue) -> u8 {
+    match value {
+        LoxValue::Nil | LoxValue::Uninitialized => 0,
+        LoxValue::Boolean(_) => 1,
+        LoxValue::Number(_) => 2,
+        LoxValue::String(_) => 3,
+        LoxValue::List(_) => 4,
+        LoxValue::Map(_) => 5,
+        LoxValue::Callable(_) => 6,
+        LoxValue::Instance(_) => 7,
+    }
+}
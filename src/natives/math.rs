@@ -0,0 +1,173 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    environment::Environment,
+    interpreter::{Interpreter, LoxValue, RuntimeEvent},
+    token::Token,
+};
+
+use super::{define, define_variadic, error};
+
+pub fn define_natives(environment: &mut Environment) {
+    define_variadic(environment, "min", 1, min);
+    define_variadic(environment, "max", 1, max);
+    define(environment, "clamp", 3, clamp);
+    define(environment, "sqrt", 1, sqrt);
+    define(environment, "abs", 1, abs);
+    define(environment, "floor", 1, floor);
+    define(environment, "ceil", 1, ceil);
+    define(environment, "divmod", 2, divmod);
+}
+
+pub(crate) type PureFunction = fn(f64) -> f64;
+
+// Natives of a single number that always give the same result for it and have no side effects,
+// so constant folding can call them before running, see `constant_folding`
+const PURE_FUNCTIONS: [(&str, PureFunction); 4] = [
+    ("sqrt", f64::sqrt),
+    ("abs", f64::abs),
+    ("floor", f64::floor),
+    ("ceil", f64::ceil),
+];
+
+pub(crate) fn pure_function(name: &str) -> Option<PureFunction> {
+    PURE_FUNCTIONS
+        .iter()
+        .find(|(pure_name, _)| *pure_name == name)
+        .map(|(_, function)| *function)
+}
+
+fn sqrt(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    apply_pure("sqrt", arguments, call_token)
+}
+
+fn abs(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    apply_pure("abs", arguments, call_token)
+}
+
+fn floor(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    apply_pure("floor", arguments, call_token)
+}
+
+fn ceil(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    apply_pure("ceil", arguments, call_token)
+}
+
+fn apply_pure(
+    name: &str,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    let function = pure_function(name).expect("Only called for pure functions");
+    match arguments[0].as_number() {
+        Some(number) => Ok(LoxValue::number(function(number))),
+        None => error(call_token, &format!("{}() expects a number.", name)),
+    }
+}
+
+fn min(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    extreme("min", arguments, call_token, f64::min)
+}
+
+fn max(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    extreme("max", arguments, call_token, f64::max)
+}
+
+// Folds either the numbers passed as arguments or the elements of a single list argument
+fn extreme(
+    name: &str,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+    pick: fn(f64, f64) -> f64,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    let values = match arguments.as_slice() {
+        [single] => match single.as_ref() {
+            LoxValue::List(elements) => elements.borrow().clone(),
+            _ => arguments,
+        },
+        _ => arguments,
+    };
+
+    let mut result: Option<f64> = None;
+    for value in values.iter() {
+        match value.as_ref() {
+            LoxValue::Number(number) => {
+                result = Some(result.map_or(*number, |result| pick(result, *number)))
+            }
+            _ => return error(call_token, &format!("{}() expects numbers.", name)),
+        }
+    }
+
+    match result {
+        Some(result) => Ok(Rc::new(LoxValue::Number(result))),
+        None => error(call_token, &format!("{}() expects a non-empty list.", name)),
+    }
+}
+
+// The value limited to the range from `low` to `high`, both inclusive
+fn clamp(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    let [LoxValue::Number(value), LoxValue::Number(low), LoxValue::Number(high)] =
+        [0, 1, 2].map(|i| arguments[i].as_ref())
+    else {
+        return error(call_token, "clamp() expects numbers.");
+    };
+
+    // NaN bounds are rejected too, as `f64::clamp` would panic on them
+    if low.is_nan() || high.is_nan() || low > high {
+        return error(
+            call_token,
+            "clamp() expects the lower bound to be at most the upper bound.",
+        );
+    }
+
+    Ok(Rc::new(LoxValue::Number(value.clamp(*low, *high))))
+}
+
+// `[quotient, remainder]` of the Euclidean division, so that `a == quotient * b + remainder`
+// with a remainder that is never negative. For a positive `b` the quotient is `floor(a / b)`.
+fn divmod(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    let [LoxValue::Number(a), LoxValue::Number(b)] = [0, 1].map(|i| arguments[i].as_ref()) else {
+        return error(call_token, "divmod() expects numbers.");
+    };
+    if *b == 0.0 {
+        return error(call_token, "Cannot divide by 0.");
+    }
+
+    let result = vec![
+        LoxValue::number(a.div_euclid(*b)),
+        LoxValue::number(a.rem_euclid(*b)),
+    ];
+    Ok(Rc::new(LoxValue::List(Rc::new(RefCell::new(result)))))
+}
@@ -0,0 +1,46 @@
+use std::rc::Rc;
+
+use crate::{
+    interpreter::{Interpreter, LoxValue, RuntimeEvent},
+    lox_callable::NativeFn,
+    symbol::Symbol,
+    token::Token,
+};
+
+use super::error;
+
+// More digits than this don't make a difference for an f64, `sprintf()` allows as many
+pub(super) const MAX_FIXED_DIGITS: usize = 100;
+
+// The methods numbers have, with their arity not counting the number itself
+pub fn method(name: Symbol) -> Option<(usize, NativeFn)> {
+    match name.as_str() {
+        "toFixed" => Some((1, to_fixed)),
+        _ => None,
+    }
+}
+
+// The number with exactly the given amount of decimal places, e.g. `(3.14159).toFixed(2)` is "3.14"
+fn to_fixed(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    let (LoxValue::Number(number), LoxValue::Number(digits)) =
+        (arguments[0].as_ref(), arguments[1].as_ref())
+    else {
+        return error(call_token, "toFixed() expects a number of digits.");
+    };
+
+    if digits.fract() != 0.0 || !(0.0..=MAX_FIXED_DIGITS as f64).contains(digits) {
+        return error(
+            call_token,
+            "toFixed() expects a whole number of digits from 0 to 100.",
+        );
+    }
+
+    Ok(Rc::new(LoxValue::String(format!(
+        "{:.*}",
+        *digits as usize, number
+    ))))
+}
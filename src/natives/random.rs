@@ -0,0 +1,98 @@
+use std::rc::Rc;
+
+use crate::{
+    environment::Environment,
+    interpreter::{Interpreter, LoxValue, RuntimeEvent},
+    token::Token,
+};
+
+use super::{define, error};
+
+// Largest integer below which every integer is exactly representable as a f64
+const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+
+pub fn define_natives(environment: &mut Environment) {
+    define(environment, "seedRandom", 1, seed_random);
+    define(environment, "random", 0, random);
+    define(environment, "randInt", 2, rand_int);
+}
+
+// A SplitMix64 generator, small and good enough for scripts but not for cryptography
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    // Seeded from the current time, so every run gets different numbers
+    pub fn from_time() -> Rng {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("Time went backwards");
+        Rng::new(now.as_nanos() as u64)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniformly distributed in [0, 1), using the 53 bits a f64 can hold exactly
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn seed_random(
+    interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    match arguments[0].as_ref() {
+        LoxValue::Number(seed) if seed.fract() == 0.0 && seed.abs() <= MAX_SAFE_INTEGER => {
+            interpreter.seed_random(*seed as i64 as u64);
+            Ok(LoxValue::nil())
+        }
+        _ => error(call_token, "seedRandom() expects an integer seed."),
+    }
+}
+
+fn random(
+    interpreter: &Interpreter,
+    _arguments: Vec<Rc<LoxValue>>,
+    _call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    Ok(LoxValue::number(interpreter.random()))
+}
+
+// A random integer from `low` up to and including `high`
+fn rand_int(
+    interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    let is_integer = |value: &f64| value.fract() == 0.0 && value.abs() <= MAX_SAFE_INTEGER;
+    let [LoxValue::Number(low), LoxValue::Number(high)] = [0, 1].map(|i| arguments[i].as_ref())
+    else {
+        return error(call_token, "randInt() expects integer bounds.");
+    };
+    if !is_integer(low) || !is_integer(high) {
+        return error(call_token, "randInt() expects integer bounds.");
+    }
+    if low > high {
+        return error(
+            call_token,
+            "randInt() expects the lower bound to be at most the upper bound.",
+        );
+    }
+
+    let value = low + (interpreter.random() * (high - low + 1.0)).floor();
+    // Rounding of the product can reach the excluded end of a huge range
+    Ok(LoxValue::number(value.min(*high)))
+}
@@ -0,0 +1,146 @@
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use crate::{
+    environment::Environment,
+    interpreter::{Interpreter, LoxValue, RuntimeEvent},
+    lox_callable::LoxCallable,
+    lox_class::LoxClass,
+    symbol::Symbol,
+    token::Token,
+};
+
+use super::{define, error};
+
+pub fn define_natives(environment: &mut Environment) {
+    define(environment, "methods", 1, methods);
+    define(environment, "allMethods", 1, all_methods);
+    define(environment, "inspectClosure", 1, inspect_closure);
+    define(environment, "arity", 1, arity);
+    define(environment, "classOf", 1, class_of);
+    define(environment, "apply", 2, apply);
+}
+
+// Deeper scopes are left out, a closure rarely sits in that many nested blocks
+const MAX_INSPECTED_SCOPES: usize = 16;
+
+// Names of the methods declared by the class itself, in declaration order
+fn methods(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    match arguments[0].as_ref() {
+        LoxValue::Callable(LoxCallable::Class { class }) => Ok(names(class.method_names())),
+        _ => error(call_token, "methods() expects a class."),
+    }
+}
+
+// Names of all methods an instance of the class responds to, walking up the superclass chain.
+// Overridden methods are listed once, where the subclass declares them.
+fn all_methods(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    let mut class: Option<&Rc<LoxClass>> = match arguments[0].as_ref() {
+        LoxValue::Callable(LoxCallable::Class { class }) => Some(class),
+        _ => return error(call_token, "allMethods() expects a class."),
+    };
+
+    let mut method_names: Vec<Symbol> = vec![];
+    while let Some(current) = class {
+        for name in current.method_names() {
+            if !method_names.contains(name) {
+                method_names.push(*name);
+            }
+        }
+        class = current.superclass();
+    }
+
+    Ok(names(&method_names))
+}
+
+// Describes the variables a function captured, one `{a, b}` per scope from the innermost one
+// outwards, e.g. "{count} -> {start} -> globals". Globals are not listed.
+fn inspect_closure(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    let mut environment = match arguments[0].as_ref() {
+        LoxValue::Callable(LoxCallable::Function { closure, .. }) => closure.clone(),
+        _ => return error(call_token, "inspectClosure() expects a function."),
+    };
+
+    let mut scopes = vec![];
+    loop {
+        let enclosing = environment.borrow().enclosing().cloned();
+        let Some(enclosing) = enclosing else {
+            scopes.push("globals".to_string());
+            break;
+        };
+        if scopes.len() == MAX_INSPECTED_SCOPES {
+            scopes.push("...".to_string());
+            break;
+        }
+
+        scopes.push(format!("{{{}}}", environment.borrow().names().join(", ")));
+        environment = enclosing;
+    }
+
+    Ok(Rc::new(LoxValue::String(scopes.join(" -> "))))
+}
+
+// The number of arguments a callable takes, the minimum for natives like `max` that take more.
+// For a class it's the arity of its initializer.
+fn arity(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    match arguments[0].as_ref() {
+        LoxValue::Callable(callable) => Ok(LoxValue::number(callable.arity() as f64)),
+        _ => error(call_token, "arity() expects a function or class."),
+    }
+}
+
+// The class an instance was created from, which can be called to create more of them
+fn class_of(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    match arguments[0].as_ref() {
+        LoxValue::Instance(instance) => Ok(Rc::new(LoxValue::Callable(LoxCallable::Class {
+            class: instance.borrow().class().clone(),
+        }))),
+        _ => error(call_token, "classOf() expects an instance."),
+    }
+}
+
+// Calls a callable with the elements of a list as its arguments, e.g. to forward the arguments
+// a wrapper got. The number of elements has to match the arity like for any other call.
+fn apply(
+    interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    let LoxValue::Callable(callable) = arguments[0].as_ref() else {
+        return error(call_token, "apply() expects a function or class.");
+    };
+    let LoxValue::List(list) = arguments[1].as_ref() else {
+        return error(call_token, "apply() expects a list of arguments.");
+    };
+
+    // Copied, so the callable can change the list without affecting its own arguments
+    let list_arguments: VecDeque<Rc<LoxValue>> = list.borrow().iter().cloned().collect();
+    callable.call(interpreter, list_arguments, call_token)
+}
+
+fn names(symbols: &[Symbol]) -> Rc<LoxValue> {
+    let names = symbols
+        .iter()
+        .map(|symbol| Rc::new(LoxValue::String(symbol.to_string())))
+        .collect();
+    Rc::new(LoxValue::List(Rc::new(RefCell::new(names))))
+}
@@ -0,0 +1,221 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    environment::Environment,
+    interpreter::{Interpreter, LoxValue, RuntimeEvent, Stringifyable},
+    token::Token,
+};
+
+use super::{define, define_variadic, error, number::MAX_FIXED_DIGITS};
+
+pub fn define_natives(environment: &mut Environment) {
+    define(environment, "chars", 1, chars);
+    define_variadic(environment, "format", 1, format);
+    define_variadic(environment, "sprintf", 1, sprintf);
+    define(environment, "startsWith", 2, starts_with);
+    define(environment, "endsWith", 2, ends_with);
+    define(environment, "contains", 2, contains);
+}
+
+// One string per Unicode scalar value, which is also what a for-in loop over a string yields
+pub fn characters(string: &str) -> Vec<Rc<LoxValue>> {
+    string
+        .chars()
+        .map(|character| Rc::new(LoxValue::String(character.to_string())))
+        .collect()
+}
+
+fn chars(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    match arguments[0].as_ref() {
+        LoxValue::String(string) => Ok(Rc::new(LoxValue::List(Rc::new(RefCell::new(characters(
+            string,
+        )))))),
+        _ => error(call_token, "chars() expects a string."),
+    }
+}
+
+// Every string starts with, ends with and contains the empty string
+fn starts_with(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    test_strings("startsWith", arguments, call_token, |s, prefix| {
+        s.starts_with(prefix)
+    })
+}
+
+fn ends_with(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    test_strings("endsWith", arguments, call_token, |s, suffix| {
+        s.ends_with(suffix)
+    })
+}
+
+fn contains(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    test_strings("contains", arguments, call_token, |s, needle| {
+        s.contains(needle)
+    })
+}
+
+fn test_strings(
+    name: &str,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+    test: fn(&str, &str) -> bool,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    match (arguments[0].as_string(), arguments[1].as_string()) {
+        (Some(a), Some(b)) => Ok(Rc::new(LoxValue::Boolean(test(a, b)))),
+        _ => error(call_token, &format!("{}() expects two strings.", name)),
+    }
+}
+
+// Replaces each `{}` in the template with the next argument, `{{` and `}}` stand for braces,
+// e.g. `format("{} + {} = {}", 1, 2, 3)` is "1 + 2 = 3"
+fn format(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    let LoxValue::String(template) = arguments[0].as_ref() else {
+        return error(call_token, "format() expects a template string.");
+    };
+
+    let mut values = arguments[1..].iter();
+    let mut placeholders = 0;
+    let mut formatted = String::new();
+    let mut characters = template.chars().peekable();
+    while let Some(character) = characters.next() {
+        match (character, characters.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                characters.next();
+                formatted.push(character);
+            }
+            ('{', Some('}')) => {
+                characters.next();
+                placeholders += 1;
+                if let Some(value) = values.next() {
+                    formatted.push_str(&value.stringify());
+                }
+            }
+            ('{' | '}', _) => {
+                return error(
+                    call_token,
+                    &format!("format() template has an unmatched '{}'.", character),
+                )
+            }
+            _ => formatted.push(character),
+        }
+    }
+
+    let given = arguments.len() - 1;
+    if given != placeholders {
+        return error(
+            call_token,
+            &format!(
+                "Expected {} arguments for the placeholders but got {}.",
+                placeholders, given
+            ),
+        );
+    }
+
+    Ok(Rc::new(LoxValue::String(formatted)))
+}
+
+// C-style formatting: `%s` is replaced by the next argument, `%d` by the next number without its
+// fraction, `%f` by it with 6 decimals or `%.2f` with as many as given, and `%%` stands for `%`
+fn sprintf(
+    _interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    let LoxValue::String(template) = arguments[0].as_ref() else {
+        return error(call_token, "sprintf() expects a template string.");
+    };
+
+    let mut values = arguments[1..].iter();
+    let mut specifiers = 0;
+    let mut formatted = String::new();
+    let mut characters = template.chars().peekable();
+    while let Some(character) = characters.next() {
+        if character != '%' {
+            formatted.push(character);
+            continue;
+        }
+        if characters.next_if_eq(&'%').is_some() {
+            formatted.push('%');
+            continue;
+        }
+
+        // Only `%f` takes a precision, up to what `toFixed()` allows
+        let mut specifier = String::from("%");
+        let mut precision = None;
+        if characters.next_if_eq(&'.').is_some() {
+            let mut digits = String::new();
+            while let Some(digit) = characters.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            specifier = format!("%.{}", digits);
+            precision = Some(digits.parse::<usize>().map_err(|_| ()));
+        }
+        let conversion = characters.next();
+        specifier.extend(conversion);
+
+        let precision = match (conversion, precision) {
+            (Some('s' | 'd'), None) => None,
+            (Some('f'), None) => Some(6),
+            (Some('f'), Some(Ok(precision))) if precision <= MAX_FIXED_DIGITS => Some(precision),
+            _ => {
+                return error(
+                    call_token,
+                    &format!("sprintf() doesn't support the specifier '{}'.", specifier),
+                )
+            }
+        };
+
+        specifiers += 1;
+        // Missing arguments are reported once all specifiers are counted
+        let Some(value) = values.next() else {
+            continue;
+        };
+        let text = match (conversion, value.as_ref()) {
+            (Some('s'), value) => value.stringify(),
+            // Adding 0 turns -0 into 0, so e.g. -0.5 gives "0"
+            (Some('d'), LoxValue::Number(number)) => format!("{:.0}", number.trunc() + 0.0),
+            (_, LoxValue::Number(number)) => {
+                format!("{:.*}", precision.unwrap_or_default(), number)
+            }
+            _ => {
+                return error(
+                    call_token,
+                    &format!("sprintf() expects a number for '{}'.", specifier),
+                )
+            }
+        };
+        formatted.push_str(&text);
+    }
+
+    let given = arguments.len() - 1;
+    if given != specifiers {
+        return error(
+            call_token,
+            &format!(
+                "Expected {} arguments for the specifiers but got {}.",
+                specifiers, given
+            ),
+        );
+    }
+
+    Ok(Rc::new(LoxValue::String(formatted)))
+}
@@ -0,0 +1,62 @@
+use std::rc::Rc;
+
+use crate::{
+    environment::Environment,
+    interpreter::{Interpreter, InterpreterConfig, LoxValue, RuntimeEvent},
+    token::Token,
+};
+
+use super::{define, error};
+
+// Natives reaching outside of the interpreter, each only defined if the config allows it
+pub fn define_natives(environment: &mut Environment, config: &InterpreterConfig) {
+    if config.filesystem {
+        define(environment, "readFile", 1, read_file);
+    }
+    if config.process_exit {
+        define(environment, "exit", 1, exit);
+    }
+}
+
+// The whole file as a string
+fn read_file(
+    interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    // The native can still end up in an interpreter that doesn't allow it, e.g. as a value
+    // another interpreter returned from `rlox::run`
+    if !interpreter.config().filesystem {
+        return error(call_token, "readFile() is disabled.");
+    }
+
+    let Some(path) = arguments[0].as_string() else {
+        return error(call_token, "readFile() expects a path.");
+    };
+
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(LoxValue::string(contents)),
+        Err(err) => error(call_token, &format!("Can't read file '{}': {}.", path, err)),
+    }
+}
+
+// Ends the process with the given status code right away
+fn exit(
+    interpreter: &Interpreter,
+    arguments: Vec<Rc<LoxValue>>,
+    call_token: &Token,
+) -> Result<Rc<LoxValue>, RuntimeEvent> {
+    if !interpreter.config().process_exit {
+        return error(call_token, "exit() is disabled.");
+    }
+
+    match arguments[0].as_number() {
+        Some(code) if code.fract() == 0.0 && (0.0..=255.0).contains(&code) => {
+            std::process::exit(code as i32)
+        }
+        _ => error(
+            call_token,
+            "exit() expects an integer status code from 0 to 255.",
+        ),
+    }
+}
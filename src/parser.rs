@@ -4,31 +4,128 @@ use crate::{
     error,
     expr::Expr,
     stmt::Stmt,
+    symbol::Symbol,
     token::{Literal, Token},
     token_type::TokenType,
 };
 
+// Deeper expressions are rejected, as parsing (and later evaluating) them could overflow the stack.
+// A level takes over a dozen parser frames, about 40KB in debug builds, so this leaves plenty of
+// the 8MB a main thread usually gets.
+pub(crate) const DEFAULT_MAX_NESTING: usize = 128;
+
 pub struct Parser {
     tokens: VecDeque<Token>,
     current: usize,
+    // How deeply the expression being parsed is nested
+    nesting: usize,
+    max_nesting: usize,
+    // Insert missing closing tokens instead of giving up on the statement, see `consume`
+    recover: bool,
+    // The first token inserted while parsing the current declaration
+    inserted: Option<Token>,
+    // Return the value of an expression statement ending a function body, see `implicit_return`
+    implicit_returns: bool,
+    // The nesting of the statements directly in the function body being parsed, the only ones
+    // that can end without `;` with implicit returns
+    body_nesting: Option<usize>,
+    // See `with_optional_last_semicolon`
+    optional_last_semicolon: bool,
 }
 
 #[derive(Debug)]
 struct ParseError;
 
 fn error<T>(token: &Token, message: &str) -> Result<T, ParseError> {
-    error::error_token(token, message);
+    error::error_token(error::Phase::Parse, token, message);
     Err(ParseError)
 }
 
+// The binary operator applied by a compound assignment, e.g. `+` for `+=`
+fn compound_operator(equals: &Token) -> Option<Token> {
+    let token_type = match equals.token_type {
+        TokenType::PlusEqual => TokenType::Plus,
+        TokenType::MinusEqual => TokenType::Minus,
+        TokenType::StarEqual => TokenType::Star,
+        TokenType::SlashEqual => TokenType::Slash,
+        // Not binary operators, see `TokenType::is_logical`
+        TokenType::PipePipeEqual | TokenType::AmpAmpEqual => return Some(equals.clone()),
+        _ => return None,
+    };
+    Some(Token::new(token_type, equals.lexeme.symbol(), equals.line))
+}
+
+// Turns an expression statement ending the function body into a return of its value, like an
+// arrow body. The return points at the `{` of the body for lack of a keyword.
+fn implicit_return(body: &mut Vec<Stmt>, brace: Token) {
+    if !matches!(body.last(), Some(Stmt::Expression { .. })) {
+        return;
+    }
+    if let Some(Stmt::Expression { expr }) = body.pop() {
+        body.push(Stmt::Return {
+            keyword: brace,
+            value: Some(expr),
+        });
+    }
+}
+
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Parser {
+        // Comments only matter to tools, the grammar doesn't know them
+        let mut tokens: VecDeque<Token> = tokens
+            .into_iter()
+            .filter(|token| !matches!(token.token_type, TokenType::Comment(_)))
+            .collect();
+
+        // The parser relies on ending with an `Eof`, which is never consumed
+        if tokens
+            .back()
+            .is_none_or(|token| token.token_type != TokenType::Eof)
+        {
+            let line = tokens.back().map_or(1, |token| token.line);
+            tokens.push_back(Token::new(TokenType::Eof, "", line));
+        }
+
         Parser {
-            tokens: VecDeque::from(tokens),
+            tokens,
             current: 0,
+            nesting: 0,
+            max_nesting: DEFAULT_MAX_NESTING,
+            recover: false,
+            inserted: None,
+            implicit_returns: false,
+            body_nesting: None,
+            optional_last_semicolon: false,
         }
     }
 
+    // For tools that want as much of a broken file as possible: a missing `;`, `)`, `]` or `}`,
+    // or `{` of a class or function body, is still reported, but inserted so parsing can go on.
+    // Statements that needed this are wrapped in `Stmt::Incomplete`, and the statements are
+    // returned despite the errors.
+    pub fn with_recovery(mut self) -> Parser {
+        self.recover = true;
+        self
+    }
+
+    // Make a function whose body ends with an expression statement return its value, like
+    // `fun f() { 1 + 1 }` returning 2. Its `;` can be left out before the closing `}`.
+    pub fn with_implicit_returns(mut self) -> Parser {
+        self.implicit_returns = true;
+        self
+    }
+
+    // Let the last expression of the source leave out its `;`, like `1 + 1` whose value is printed
+    pub fn with_optional_last_semicolon(mut self) -> Parser {
+        self.optional_last_semicolon = true;
+        self
+    }
+
+    pub fn with_max_nesting(mut self, max_nesting: usize) -> Parser {
+        self.max_nesting = max_nesting;
+        self
+    }
+
     pub fn parse(mut self) -> Option<Vec<Stmt>> {
         let mut statements = vec![];
         let mut has_errored = false;
@@ -45,15 +142,26 @@ impl Parser {
             };
         }
 
-        match has_errored {
+        match has_errored && !self.recover {
             true => None,
             false => Some(statements),
         }
     }
 
     fn declaration(&mut self) -> Option<Stmt> {
-        match self.declaration_impl() {
-            Ok(val) => Some(val),
+        // Only the innermost declaration missing a token gets marked
+        let enclosing_inserted = self.inserted.take();
+        let result = self.declaration_impl();
+        let inserted = std::mem::replace(&mut self.inserted, enclosing_inserted);
+
+        match result {
+            Ok(statement) => Some(match inserted {
+                Some(missing) => Stmt::Incomplete {
+                    missing,
+                    statement: Box::new(statement),
+                },
+                None => statement,
+            }),
             Err(_) => {
                 self.synchronize();
                 None
@@ -65,7 +173,12 @@ impl Parser {
         if self.match_token(TokenType::Class).is_some() {
             return self.class_declaration();
         }
-        if self.match_token(TokenType::Fun).is_some() {
+        if self.match_token(TokenType::Enum).is_some() {
+            return self.enum_declaration();
+        }
+        // `fun (` starts an anonymous function, which is parsed as an expression statement
+        if self.check(TokenType::Fun) && !self.check_next(TokenType::LeftParen) {
+            self.advance();
             return self.function("function");
         }
         if self.match_token(TokenType::Var).is_some() {
@@ -85,7 +198,24 @@ impl Parser {
             None => None,
         };
 
-        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+        // `with A, B` copies the methods of A and then B, so later mixins and the class's
+        // own methods win on conflicts. Lookups go through the class (own methods first, then
+        // B's, then A's) before its superclass chain, and `super` in copied methods refers to
+        // the class's superclass, see `rebind_super`.
+        let mut mixins = vec![];
+        if self.match_token(TokenType::With).is_some() {
+            loop {
+                mixins.push(Expr::Variable {
+                    name: self.consume(TokenType::Identifier, "Expect mixin name.")?,
+                    depth: None,
+                });
+                if self.match_token(TokenType::Comma).is_none() {
+                    break;
+                }
+            }
+        }
+
+        self.consume_body_brace("Expect '{' before class body.")?;
 
         let mut methods = vec![];
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
@@ -98,9 +228,37 @@ impl Parser {
             name,
             methods,
             superclass,
+            mixins,
         })
     }
 
+    fn enum_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let name = self.consume(TokenType::Identifier, "Expect enum name.")?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before enum variants.")?;
+
+        // Separated by commas, a trailing one is fine
+        let mut variants: Vec<Token> = vec![];
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let variant = self.consume(TokenType::Identifier, "Expect variant name.")?;
+            if variants.iter().any(|other| other.lexeme == variant.lexeme) {
+                error::error_token(
+                    error::Phase::Parse,
+                    &variant,
+                    "Already a variant with this name in this enum.",
+                );
+            }
+            variants.push(variant);
+
+            if self.match_token(TokenType::Comma).is_none() {
+                break;
+            }
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after enum variants.")?;
+
+        Ok(Stmt::Enum { name, variants })
+    }
+
     fn function(&mut self, kind: &str) -> Result<Stmt, ParseError> {
         let name = self.consume(
             TokenType::Identifier,
@@ -111,6 +269,26 @@ impl Parser {
             format!("Expect '(' after {} name.", kind).as_str(),
         )?;
 
+        // An initializer always gives `this`, its last expression is never returned
+        let is_initializer = kind == "method" && name.lexeme == Symbol::INIT;
+        let (parameters, body) = self.function_body(kind, true, !is_initializer)?;
+
+        Ok(Stmt::Function {
+            name: Rc::new(name),
+            params: Rc::new(parameters),
+            body: Rc::new(RefCell::new(body)),
+        })
+    }
+
+    // Parses everything after the opening '(' of a function: the parameter list and either a
+    // block body or an arrow body (`=> expression`), which is desugared into a single return.
+    // Declarations terminate an arrow body with ';', anonymous function expressions don't.
+    fn function_body(
+        &mut self,
+        kind: &str,
+        is_declaration: bool,
+        may_return_value: bool,
+    ) -> Result<(Vec<Token>, Vec<Stmt>), ParseError> {
         let mut parameters = vec![];
         if !self.check(TokenType::RightParen) {
             loop {
@@ -128,21 +306,45 @@ impl Parser {
 
         self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
 
-        self.consume(
-            TokenType::LeftBrace,
-            format!("Expect '{{' before {} body.", kind).as_str(),
-        )?;
+        if let Some(arrow) = self.match_token(TokenType::Arrow) {
+            let value = self.expression()?;
+            if is_declaration {
+                self.consume(
+                    TokenType::Semicolon,
+                    format!("Expect ';' after {} body.", kind).as_str(),
+                )?;
+            }
 
-        let body = self.block()?;
+            return Ok((
+                parameters,
+                vec![Stmt::Return {
+                    keyword: arrow,
+                    value: Some(value),
+                }],
+            ));
+        }
 
-        Ok(Stmt::Function {
-            name: Rc::new(name),
-            params: Rc::new(parameters),
-            body: Rc::new(RefCell::new(body)),
-        })
+        let brace =
+            self.consume_body_brace(format!("Expect '{{' before {} body.", kind).as_str())?;
+
+        // Its statements are one level deeper, see `statement`
+        let enclosing_body = self.body_nesting.replace(self.nesting + 1);
+        let body = self.block();
+        self.body_nesting = enclosing_body;
+
+        let mut body = body?;
+        if self.implicit_returns && may_return_value {
+            implicit_return(&mut body, brace);
+        }
+
+        Ok((parameters, body))
     }
 
     fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
+        if let Some(bracket) = self.match_token(TokenType::LeftBracket) {
+            return self.var_destructure(bracket);
+        }
+
         let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
 
         // self.consume(TokenType::Equal, "Expect a variable assignment ('=').")?;
@@ -159,7 +361,35 @@ impl Parser {
         Ok(Stmt::Var { name, initializer })
     }
 
+    fn var_destructure(&mut self, bracket: Token) -> Result<Stmt, ParseError> {
+        let mut names = vec![self.consume(TokenType::Identifier, "Expect variable name.")?];
+        while self.match_token(TokenType::Comma).is_some() {
+            names.push(self.consume(TokenType::Identifier, "Expect variable name.")?);
+        }
+        self.consume(TokenType::RightBracket, "Expect ']' after variable names.")?;
+
+        self.consume(TokenType::Equal, "Expect '=' after destructuring pattern.")?;
+        let initializer = self.expression()?;
+
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+
+        Ok(Stmt::VarDestructure {
+            bracket,
+            names,
+            initializer,
+        })
+    }
+
+    // Statements nest through blocks and the bodies of other statements, which counts towards the
+    // same limit as expressions
     fn statement(&mut self) -> Result<Stmt, ParseError> {
+        self.deeper(Parser::statement_impl, "Nesting too deep.")
+    }
+
+    fn statement_impl(&mut self) -> Result<Stmt, ParseError> {
         if self.match_token(TokenType::For).is_some() {
             return self.for_statement();
         }
@@ -169,12 +399,40 @@ impl Parser {
         if self.match_token(TokenType::Print).is_some() {
             return self.print_statement();
         }
+        if self.match_token(TokenType::Eprint).is_some() {
+            return self.eprint_statement();
+        }
+        if let Some(keyword) = self.match_token(TokenType::Assert) {
+            return self.assert_statement(keyword);
+        }
         if let Some(keyword) = self.match_token(TokenType::Return) {
             return self.return_statement(keyword);
         }
         if self.match_token(TokenType::While).is_some() {
             return self.while_statement();
         }
+        if self.match_token(TokenType::Loop).is_some() {
+            return self.loop_statement();
+        }
+        if let Some(keyword) = self.match_token(TokenType::Break) {
+            self.consume(TokenType::Semicolon, "Expect ';' after 'break'.")?;
+            return Ok(Stmt::Break { keyword });
+        }
+        if let Some(keyword) = self.match_token(TokenType::Continue) {
+            self.consume(TokenType::Semicolon, "Expect ';' after 'continue'.")?;
+            return Ok(Stmt::Continue { keyword });
+        }
+        if let Some(keyword) = self.match_token(TokenType::Switch) {
+            return self.switch_statement(keyword);
+        }
+        if let Some(keyword) = self.match_token(TokenType::Throw) {
+            let value = self.expression()?;
+            self.consume(TokenType::Semicolon, "Expect ';' after thrown value.")?;
+            return Ok(Stmt::Throw { keyword, value });
+        }
+        if self.match_token(TokenType::Try).is_some() {
+            return self.try_statement();
+        }
         if self.match_token(TokenType::LeftBrace).is_some() {
             return Ok(Stmt::Block {
                 statements: self.block()?,
@@ -188,9 +446,14 @@ impl Parser {
         self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
 
         // initializer
+        let mut declares_variable = false;
         let initializer = if self.match_token(TokenType::Semicolon).is_some() {
             None
         } else if self.match_token(TokenType::Var).is_some() {
+            if self.check_next(TokenType::In) {
+                return self.for_in_statement();
+            }
+            declares_variable = true;
             Some(self.var_declaration()?)
         } else {
             Some(self.expression_statement()?)
@@ -213,19 +476,15 @@ impl Parser {
         self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
 
         // body
-        let mut body = self.statement()?;
+        let body = self.statement()?;
 
         // desugaring for loop into known statements
 
-        if let Some(increment) = increment {
-            body = Stmt::Block {
-                statements: vec![body, Stmt::Expression { expr: increment }],
-            }
-        }
-
-        body = Stmt::While {
+        let mut body = Stmt::While {
             condition,
             body: Box::new(body),
+            increment,
+            scope_per_iteration: declares_variable,
         };
 
         if let Some(initializer) = initializer {
@@ -237,6 +496,22 @@ impl Parser {
         Ok(body)
     }
 
+    // The rest of `for (var name in iterable) body` after the `var`
+    fn for_in_statement(&mut self) -> Result<Stmt, ParseError> {
+        let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
+        let keyword = self.advance();
+        let iterable = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after for-in clause.")?;
+        let body = self.statement()?;
+
+        Ok(Stmt::ForIn {
+            name,
+            keyword,
+            iterable,
+            body: Box::new(body),
+        })
+    }
+
     fn return_statement(&mut self, keyword: Token) -> Result<Stmt, ParseError> {
         let value = match self.check(TokenType::Semicolon) {
             true => None,
@@ -257,9 +532,108 @@ impl Parser {
         Ok(Stmt::While {
             condition,
             body: Box::new(body),
+            increment: None,
+            scope_per_iteration: false,
+        })
+    }
+
+    // `loop { ... }` runs until a break, desugared to a while loop with a condition of true
+    fn loop_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'loop'.")?;
+        let statements = self.block()?;
+
+        Ok(Stmt::While {
+            condition: Expr::Literal {
+                value: Literal::Boolean(true),
+            },
+            body: Box::new(Stmt::Block { statements }),
+            increment: None,
+            scope_per_iteration: false,
+        })
+    }
+
+    fn switch_statement(&mut self, keyword: Token) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'switch'.")?;
+        let value = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after switch value.")?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before switch cases.")?;
+
+        let mut cases = vec![];
+        let mut default = None;
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if self.match_token(TokenType::Case).is_some() {
+                let case = self.expression()?;
+                self.consume(TokenType::Colon, "Expect ':' after case value.")?;
+                cases.push((case, self.case_body()));
+            } else if let Some(keyword) = self.match_token(TokenType::Default) {
+                self.consume(TokenType::Colon, "Expect ':' after 'default'.")?;
+                if default.is_some() {
+                    // Reported without giving up on the rest of the switch
+                    let _ = error::<()>(&keyword, "A switch can only have one default case.");
+                }
+                default = Some(self.case_body());
+            } else {
+                error(self.peek(), "Expect 'case' or 'default'.")?;
+            }
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after switch cases.")?;
+
+        Ok(Stmt::Switch {
+            keyword,
+            value,
+            cases,
+            default,
         })
     }
 
+    fn try_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'try'.")?;
+        let body = self.block()?;
+
+        let mut catch = None;
+        if self.match_token(TokenType::Catch).is_some() {
+            self.consume(TokenType::LeftParen, "Expect '(' after 'catch'.")?;
+            let name = self.consume(TokenType::Identifier, "Expect exception name.")?;
+            self.consume(TokenType::RightParen, "Expect ')' after exception name.")?;
+            self.consume(TokenType::LeftBrace, "Expect '{' after catch clause.")?;
+            catch = Some((name, self.block()?));
+        }
+
+        let mut finally = None;
+        if self.match_token(TokenType::Finally).is_some() {
+            self.consume(TokenType::LeftBrace, "Expect '{' after 'finally'.")?;
+            finally = Some(self.block()?);
+        }
+
+        if catch.is_none() && finally.is_none() {
+            return error(self.peek(), "Expect 'catch' or 'finally' after try block.");
+        }
+
+        Ok(Stmt::Try {
+            body,
+            catch,
+            finally,
+        })
+    }
+
+    // The statements up to the next case or the end of the switch
+    fn case_body(&mut self) -> Vec<Stmt> {
+        let mut statements = vec![];
+
+        while !self.check(TokenType::Case)
+            && !self.check(TokenType::Default)
+            && !self.check(TokenType::RightBrace)
+            && !self.is_at_end()
+        {
+            if let Some(declaration) = self.declaration() {
+                statements.push(declaration);
+            }
+        }
+
+        statements
+    }
+
     fn if_statement(&mut self) -> Result<Stmt, ParseError> {
         self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
         let condition = self.expression()?;
@@ -300,53 +674,169 @@ impl Parser {
         Ok(Stmt::Print { expr: value })
     }
 
+    fn eprint_statement(&mut self) -> Result<Stmt, ParseError> {
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+        Ok(Stmt::Eprint { expr: value })
+    }
+
+    fn assert_statement(&mut self, keyword: Token) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'assert'.")?;
+        let condition = self.expression()?;
+        let message = match self.match_token(TokenType::Comma) {
+            Some(_) => Some(self.expression()?),
+            None => None,
+        };
+        self.consume(TokenType::RightParen, "Expect ')' after assertion.")?;
+        self.consume(TokenType::Semicolon, "Expect ';' after assertion.")?;
+
+        Ok(Stmt::Assert {
+            keyword,
+            condition,
+            message,
+        })
+    }
+
     fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
         let expr = self.expression()?;
-        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        // The semicolon can be left out after the last expression of the source in the modes that
+        // allow it, and of a function body if its value can be returned implicitly
+        let ends_source = self.optional_last_semicolon && self.is_at_end();
+        let ends_body = self.implicit_returns
+            && self.body_nesting == Some(self.nesting)
+            && self.check(TokenType::RightBrace);
+        if !ends_source && !ends_body {
+            self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        }
         Ok(Stmt::Expression { expr })
     }
 
     fn expression(&mut self) -> Result<Expr, ParseError> {
-        self.assignment()
+        self.nested(Parser::assignment)
+    }
+
+    // Wraps the rules through which expressions recurse, to report too deep nesting gracefully
+    fn nested(
+        &mut self,
+        rule: fn(&mut Parser) -> Result<Expr, ParseError>,
+    ) -> Result<Expr, ParseError> {
+        self.deeper(rule, "Expression nesting too deep.")
+    }
+
+    fn deeper<T>(
+        &mut self,
+        rule: fn(&mut Parser) -> Result<T, ParseError>,
+        message: &str,
+    ) -> Result<T, ParseError> {
+        if self.nesting >= self.max_nesting {
+            return error(self.peek(), message);
+        }
+
+        self.nesting += 1;
+        let result = rule(self);
+        self.nesting -= 1;
+        result
     }
 
     fn assignment(&mut self) -> Result<Expr, ParseError> {
         let expr = self.ternary()?;
 
-        if let Some(equals) = self.match_token(TokenType::Equal) {
-            let value = self.assignment()?;
+        if let Some(equals) = self.match_tokens(vec![
+            TokenType::Equal,
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+            TokenType::PipePipeEqual,
+            TokenType::AmpAmpEqual,
+        ]) {
+            return self.assign(expr, equals);
+        }
 
-            match expr {
-                Expr::Variable { name, depth: None } => {
-                    return Ok(Expr::Assign {
-                        name,
-                        value: Box::new(value),
+        Ok(expr)
+    }
+
+    // The assignment of the value after `equals` to the target before it. Separate from
+    // `assignment`, see `call_suffixes`.
+    fn assign(&mut self, target: Expr, equals: Token) -> Result<Expr, ParseError> {
+        let value = self.nested(Parser::assignment)?;
+        let operator = compound_operator(&equals);
+
+        match target {
+            // `a ||= b` is `a or (a = b)`, the value is only evaluated if it's assigned
+            Expr::Variable { name, depth: None } if equals.token_type.is_logical() => {
+                Ok(Expr::Logical {
+                    left: Box::new(Expr::Variable {
+                        name: name.clone(),
                         depth: None,
-                    })
-                }
-                Expr::Get { object, name } => {
-                    return Ok(Expr::Set {
-                        object,
+                    }),
+                    operator: equals,
+                    right: Box::new(Expr::Assign {
                         name,
                         value: Box::new(value),
-                    })
-                }
-                _ => return error(&equals, "Invalid assignment target."),
+                        depth: None,
+                    }),
+                })
             }
+            Expr::Variable { name, depth: None } => {
+                // Reading a variable has no side effects, so `a += 1` is just `a = a + 1`
+                let value = match operator {
+                    Some(operator) => Expr::Binary {
+                        left: Box::new(Expr::Variable {
+                            name: name.clone(),
+                            depth: None,
+                        }),
+                        operator,
+                        right: Box::new(value),
+                    },
+                    None => value,
+                };
+                Ok(Expr::Assign {
+                    name,
+                    value: Box::new(value),
+                    depth: None,
+                })
+            }
+            Expr::Get { object, name } => Ok(Expr::Set {
+                object,
+                name,
+                value: Box::new(value),
+                operator,
+            }),
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => Ok(Expr::IndexSet {
+                object,
+                bracket,
+                index,
+                value: Box::new(value),
+                operator,
+            }),
+            _ => error(&equals, "Invalid assignment target."),
         }
-
-        Ok(expr)
     }
 
     fn ternary(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.or()?;
 
+        // `a ?: b` is `a` if it's truthy and `b` otherwise, grouping to the right like the ternary
+        if let Some(operator) = self.match_token(TokenType::QuestionColon) {
+            let right = self.nested(Parser::ternary)?;
+            return Ok(Expr::Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            });
+        }
+
         while let Some(_operator) = self.match_token(TokenType::QuestionMark) {
-            let then = self.ternary()?;
+            let then = self.nested(Parser::ternary)?;
 
             match self.match_token(TokenType::Colon) {
                 Some(_) => {
-                    let r#else = self.ternary()?;
+                    let r#else = self.nested(Parser::ternary)?;
                     expr = Expr::Conditional {
                         condition: Box::new(expr),
                         then: Box::new(then),
@@ -408,13 +898,34 @@ impl Parser {
     }
 
     fn comparison(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.term()?;
+        let mut expr = self.shift()?;
 
         while let Some(operator) = self.match_tokens(vec![
             TokenType::Greater,
             TokenType::GreaterEqual,
             TokenType::Less,
             TokenType::LessEqual,
+            TokenType::Is,
+        ]) {
+            let right = self.shift()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            }
+        }
+
+        Ok(expr)
+    }
+
+    // Binds tighter than comparisons but looser than arithmetic like in C, `1 << 2 + 1` is 8
+    fn shift(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.term()?;
+
+        while let Some(operator) = self.match_tokens(vec![
+            TokenType::LessLess,
+            TokenType::GreaterGreater,
+            TokenType::GreaterGreaterGreater,
         ]) {
             let right = self.term()?;
             expr = Expr::Binary {
@@ -459,19 +970,32 @@ impl Parser {
 
     fn unary(&mut self) -> Result<Expr, ParseError> {
         if let Some(operator) = self.match_tokens(vec![TokenType::Bang, TokenType::Minus]) {
-            let right = self.unary()?;
+            let right = self.nested(Parser::unary)?;
             return Ok(Expr::Unary {
                 operator,
                 right: Box::new(right),
             });
         }
 
+        if let Some(keyword) = self.match_token(TokenType::Delete) {
+            return match self.call()? {
+                Expr::Get { object, name } => Ok(Expr::Delete { object, name }),
+                _ => error(&keyword, "Expect a property to delete."),
+            };
+        }
+
         self.call()
     }
 
     fn call(&mut self) -> Result<Expr, ParseError> {
-        let mut expr = self.primary()?;
+        let expr = self.primary()?;
+        self.call_suffixes(expr)
+    }
 
+    // Calls, property accesses, cascades and indexing after a primary expression. Separate from
+    // `call`, which is part of every nesting level, so that its stack frame stays small. The
+    // same goes for `assign`.
+    fn call_suffixes(&mut self, mut expr: Expr) -> Result<Expr, ParseError> {
         loop {
             if self.match_token(TokenType::LeftParen).is_some() {
                 expr = self.finish_call(expr)?;
@@ -482,6 +1006,16 @@ impl Parser {
                     object: Box::new(expr),
                     name,
                 }
+            } else if self.match_token(TokenType::DotDot).is_some() {
+                expr = self.cascade(expr)?;
+            } else if self.match_token(TokenType::LeftBracket).is_some() {
+                let index = self.expression()?;
+                let bracket = self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+                expr = Expr::Index {
+                    object: Box::new(expr),
+                    bracket,
+                    index: Box::new(index),
+                }
             } else {
                 break;
             }
@@ -490,7 +1024,35 @@ impl Parser {
         Ok(expr)
     }
 
+    // The rest of `object..method(arguments)` after the `..`
+    fn cascade(&mut self, object: Expr) -> Result<Expr, ParseError> {
+        let name = self.consume(TokenType::Identifier, "Expect method name after '..'.")?;
+        self.consume(
+            TokenType::LeftParen,
+            "Expect '(' after cascaded method name.",
+        )?;
+        let (arguments, closing_paren) = self.arguments()?;
+
+        Ok(Expr::Cascade {
+            object: Box::new(object),
+            name,
+            closing_paren,
+            arguments,
+        })
+    }
+
     fn finish_call(&mut self, callee: Expr) -> Result<Expr, ParseError> {
+        let (arguments, closing_paren) = self.arguments()?;
+
+        Ok(Expr::Call {
+            callee: Box::new(callee),
+            closing_paren,
+            arguments,
+        })
+    }
+
+    // The arguments after the opening paren of a call, and the closing paren
+    fn arguments(&mut self) -> Result<(Vec<Expr>, Token), ParseError> {
         let mut arguments = vec![];
 
         if !self.check(TokenType::RightParen) {
@@ -507,11 +1069,7 @@ impl Parser {
 
         let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
 
-        Ok(Expr::Call {
-            callee: Box::new(callee),
-            closing_paren: paren,
-            arguments,
-        })
+        Ok((arguments, paren))
     }
 
     fn primary(&mut self) -> Result<Expr, ParseError> {
@@ -554,17 +1112,31 @@ impl Parser {
             return Ok(Expr::Super {
                 keyword,
                 method,
-                depth: 0,
+                depth: None,
+                this_depth: None,
+            });
+        }
+
+        if self.match_token(TokenType::Fun).is_some() {
+            self.consume(TokenType::LeftParen, "Expect '(' after 'fun'.")?;
+            let (params, body) = self.function_body("function", false, true)?;
+            return Ok(Expr::Function {
+                params: Rc::new(params),
+                body: Rc::new(RefCell::new(body)),
             });
         }
 
         if let Some(token) = self.match_token(TokenType::This) {
             return Ok(Expr::This {
                 keyword: token,
-                depth: 0,
+                depth: None,
             });
         }
 
+        if self.match_token(TokenType::LeftBracket).is_some() {
+            return self.list_or_map();
+        }
+
         if self.match_token(TokenType::LeftParen).is_some() {
             let expr = self.expression()?;
             self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
@@ -578,6 +1150,40 @@ impl Parser {
         error(self.peek(), "Expect expression.")
     }
 
+    // Both start with '[', a ':' after the first element makes it a map and `[:]` is the empty map.
+    // Braces would clash with blocks, e.g. in `for (;{};)`.
+    fn list_or_map(&mut self) -> Result<Expr, ParseError> {
+        if self.match_token(TokenType::Colon).is_some() {
+            self.consume(TokenType::RightBracket, "Expect ']' after map entries.")?;
+            return Ok(Expr::Map { entries: vec![] });
+        }
+        if self.match_token(TokenType::RightBracket).is_some() {
+            return Ok(Expr::List { elements: vec![] });
+        }
+
+        let first = self.expression()?;
+
+        if self.match_token(TokenType::Colon).is_some() {
+            let mut entries = vec![(first, self.expression()?)];
+            while self.match_token(TokenType::Comma).is_some() {
+                let key = self.expression()?;
+                self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+                entries.push((key, self.expression()?));
+            }
+            self.consume(TokenType::RightBracket, "Expect ']' after map entries.")?;
+
+            return Ok(Expr::Map { entries });
+        }
+
+        let mut elements = vec![first];
+        while self.match_token(TokenType::Comma).is_some() {
+            elements.push(self.expression()?);
+        }
+        self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+
+        Ok(Expr::List { elements })
+    }
+
     // After we hit a parse error, we discard tokens until we can continue parsing (until we encounter a new statement)
     fn synchronize(&mut self) {
         let mut previous = self.advance();
@@ -589,12 +1195,19 @@ impl Parser {
 
             match self.peek().token_type {
                 TokenType::Class
+                | TokenType::Enum
                 | TokenType::Fun
                 | TokenType::Var
                 | TokenType::For
                 | TokenType::If
                 | TokenType::While
+                | TokenType::Loop
                 | TokenType::Print
+                | TokenType::Eprint
+                | TokenType::Assert
+                | TokenType::Switch
+                | TokenType::Throw
+                | TokenType::Try
                 | TokenType::Return => {
                     return;
                 }
@@ -606,13 +1219,42 @@ impl Parser {
     }
 
     fn consume(&mut self, t: TokenType, error_msg: &str) -> Result<Token, ParseError> {
-        if self.check(t) {
+        if self.check(t.clone()) {
             return Ok(self.advance());
         }
 
+        let closing = matches!(
+            t,
+            TokenType::Semicolon
+                | TokenType::RightParen
+                | TokenType::RightBracket
+                | TokenType::RightBrace
+        );
+        if self.recover && closing {
+            return Ok(self.insert(t, error_msg));
+        }
+
         error(self.peek(), error_msg)?
     }
 
+    // Like `consume` for the `{` opening a class or function body, which recovery inserts as
+    // well. The body then goes on until the next `}`, so the rest of the file still parses.
+    fn consume_body_brace(&mut self, error_msg: &str) -> Result<Token, ParseError> {
+        if self.recover && !self.check(TokenType::LeftBrace) {
+            return Ok(self.insert(TokenType::LeftBrace, error_msg));
+        }
+
+        self.consume(TokenType::LeftBrace, error_msg)
+    }
+
+    // Reports the token as missing and pretends it was there
+    fn insert(&mut self, t: TokenType, error_msg: &str) -> Token {
+        error::error_token(error::Phase::Parse, self.peek(), error_msg);
+        let inserted = Token::new(t, "", self.peek().line);
+        self.inserted.get_or_insert_with(|| inserted.clone());
+        inserted
+    }
+
     fn match_tokens(&mut self, types: Vec<TokenType>) -> Option<Token> {
         for t in types {
             if self.check(t) {
@@ -661,6 +1303,13 @@ impl Parser {
         self.pre_check() && self.peek().token_type == t
     }
 
+    // Looks at the token after the current one
+    fn check_next(&self, t: TokenType) -> bool {
+        self.tokens
+            .get(1)
+            .is_some_and(|token| token.token_type == t)
+    }
+
     fn pre_check(&self) -> bool {
         !self.is_at_end()
     }
@@ -681,6 +1330,268 @@ impl Parser {
     }
 
     fn peek(&self) -> &Token {
-        self.tokens.front().unwrap()
+        self.tokens
+            .front()
+            .expect("The Eof token should never be consumed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Parser;
+    use crate::{
+        error,
+        expr::Expr,
+        scanner::Scanner,
+        stmt::Stmt,
+        token::{Literal, Token},
+        token_type::TokenType,
+    };
+
+    const SOURCE: &str = r#"
+        class A < B { init(x) { this.x = x; } get() => super.get() + this.x; }
+        fun f(a, b) { if (a and !b) return [a, b]; else { return ["k": a]; } }
+        for (var i = 0; i < 10; i = i + 1) { while (true) print i > 1 ? -i : i; }
+        assert f(1, nil) != [:], "message";
+        var g = fun (x) { return x or false; };
+        loop { try { throw 1; } catch (e) { break; } finally { continue; } }
+    "#;
+
+    fn parse_without_panicking(tokens: Vec<Token>) {
+        let description = format!("{:?}", tokens);
+        let result = std::panic::catch_unwind(|| {
+            error::collect_diagnostics(|| Parser::new(tokens.clone()).parse());
+            error::collect_diagnostics(|| Parser::new(tokens).with_recovery().parse());
+        });
+        assert!(result.is_ok(), "Parser panicked on {}", description);
+    }
+
+    #[test]
+    fn test_truncated_token_streams_do_not_panic() {
+        let tokens = Scanner::new(SOURCE.to_string()).scan_tokens();
+
+        // Prefixes lack the Eof token, so this also covers streams without one
+        for end in 0..=tokens.len() {
+            parse_without_panicking(tokens[..end].to_vec());
+        }
+    }
+
+    #[test]
+    fn test_random_token_streams_do_not_panic() {
+        let pool = Scanner::new(SOURCE.to_string()).scan_tokens();
+
+        // A xorshift generator keeps the test deterministic without extra dependencies
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+
+        for _ in 0..2000 {
+            let length = next() % 40;
+            let mut tokens: Vec<Token> = (0..length)
+                .map(|_| pool[next() % pool.len()].clone())
+                .collect();
+            if next() % 2 == 0 {
+                tokens.push(Token::new(TokenType::Eof, "", 1));
+            }
+            parse_without_panicking(tokens);
+        }
+    }
+
+    #[test]
+    fn test_recovery_inserts_a_missing_semicolon() {
+        let source = "var a = 1;\nprint a\nprint a + 1;\nvar b = (a;";
+        let (statements, diagnostics) = error::collect_diagnostics(|| {
+            let tokens = Scanner::new(source.to_string()).scan_tokens();
+            Parser::new(tokens).with_recovery().parse()
+        });
+
+        let statements = statements.expect("Recovery returns the statements");
+        assert_eq!(statements.len(), 4);
+        assert!(matches!(statements[0], Stmt::Var { .. }));
+        assert!(matches!(
+            &statements[1],
+            Stmt::Incomplete { missing, statement }
+                if missing.token_type == TokenType::Semicolon
+                    && matches!(**statement, Stmt::Print { .. })
+        ));
+        assert!(matches!(statements[2], Stmt::Print { .. }));
+        assert!(matches!(
+            &statements[3],
+            Stmt::Incomplete { missing, .. } if missing.token_type == TokenType::RightParen
+        ));
+
+        let messages: Vec<String> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "[line 3] Error at 'print': Expect ';' after value.",
+                "[line 4] Error at ';': Expect ')' after expression."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_without_recovery_nothing_is_returned() {
+        let (statements, diagnostics) = error::collect_diagnostics(|| {
+            let tokens = Scanner::new("print 1\nprint 2;".to_string()).scan_tokens();
+            Parser::new(tokens).parse()
+        });
+
+        assert!(statements.is_none());
+        assert_eq!(diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn test_loop_is_a_while_true_loop() {
+        let tokens = Scanner::new("loop { break; }".to_string()).scan_tokens();
+        let statements = Parser::new(tokens).parse().expect("Source should parse");
+
+        assert!(matches!(
+            &statements[..],
+            [Stmt::While {
+                condition: Expr::Literal {
+                    value: Literal::Boolean(true)
+                },
+                body,
+                increment: None,
+                scope_per_iteration: false,
+            }] if matches!(&**body, Stmt::Block { statements } if statements.len() == 1)
+        ));
+    }
+
+    #[test]
+    fn test_enum_declaration_lists_its_variants() {
+        let tokens = Scanner::new("enum Color { RED, GREEN, }".to_string()).scan_tokens();
+        let statements = Parser::new(tokens).parse().expect("Source should parse");
+
+        let [Stmt::Enum { name, variants }] = &statements[..] else {
+            panic!("Expected an enum, got {:?}", statements);
+        };
+        assert_eq!(name.lexeme.as_str(), "Color");
+        let variants: Vec<&str> = variants.iter().map(|v| v.lexeme.as_str()).collect();
+        assert_eq!(variants, ["RED", "GREEN"]);
+    }
+
+    #[test]
+    fn test_implicit_returns_only_change_the_last_expression_of_a_body() {
+        let source = "fun f() { 1; 1 + 1 }\nclass A { init() { this.a = 1 } }";
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let statements = Parser::new(tokens)
+            .with_implicit_returns()
+            .parse()
+            .expect("Source should parse");
+
+        let Stmt::Function { body, .. } = &statements[0] else {
+            panic!("Expected a function, got {:?}", statements[0]);
+        };
+        assert!(matches!(
+            &body.borrow()[..],
+            [Stmt::Expression { .. }, Stmt::Return { value: Some(_), .. }]
+        ));
+
+        let Stmt::Class { methods, .. } = &statements[1] else {
+            panic!("Expected a class, got {:?}", statements[1]);
+        };
+        let Stmt::Function { body, .. } = &methods[0] else {
+            panic!("Expected a method, got {:?}", methods[0]);
+        };
+        assert!(matches!(&body.borrow()[..], [Stmt::Expression { .. }]));
+    }
+
+    #[test]
+    fn test_leaving_out_the_last_semicolon_is_opt_in() {
+        let parse = |optional_last_semicolon| {
+            error::collect_diagnostics(|| {
+                let tokens = Scanner::new("print 1; 1 + 1".to_string()).scan_tokens();
+                let parser = Parser::new(tokens);
+                match optional_last_semicolon {
+                    true => parser.with_optional_last_semicolon().parse(),
+                    false => parser.parse(),
+                }
+            })
+        };
+
+        let (statements, diagnostics) = parse(false);
+        assert!(statements.is_none());
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "[line 1] Error at end: Expect ';' after expression."
+        );
+
+        let (statements, diagnostics) = parse(true);
+        assert_eq!(statements.map(|statements| statements.len()), Some(2));
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_implicit_returns_only_allow_leaving_out_the_semicolon_ending_a_body() {
+        let parse = |source: &str| {
+            error::collect_diagnostics(|| {
+                let tokens = Scanner::new(source.to_string()).scan_tokens();
+                Parser::new(tokens).with_implicit_returns().parse()
+            })
+        };
+
+        let (statements, diagnostics) = parse("fun f() { fun (c) { c } }");
+        assert!(statements.is_some());
+        assert!(diagnostics.is_empty());
+
+        for source in [
+            "fun f(c) { if (c) { 1 } 2 }",
+            "fun f(c) { if (c) 1 }",
+            "fun f(c) { switch (c) { case 1: 1 } }",
+            "{ g = 4 }",
+        ] {
+            let (statements, diagnostics) = parse(source);
+            assert!(statements.is_none(), "{}", source);
+            assert_eq!(
+                diagnostics[0].message, "Expect ';' after expression.",
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_recovery_inserts_a_missing_body_brace() {
+        let source = "fun f(a)\n  print a;\n}\nclass A\n  m() {}\n}\nvar b = 1;";
+        let (statements, diagnostics) = error::collect_diagnostics(|| {
+            let tokens = Scanner::new(source.to_string()).scan_tokens();
+            Parser::new(tokens).with_recovery().parse()
+        });
+
+        let statements = statements.expect("Recovery returns the statements");
+        assert_eq!(statements.len(), 3);
+        assert!(matches!(
+            &statements[0],
+            Stmt::Incomplete { missing, statement }
+                if missing.token_type == TokenType::LeftBrace
+                    && matches!(&**statement, Stmt::Function { body, .. } if body.borrow().len() == 1)
+        ));
+        assert!(matches!(
+            &statements[1],
+            Stmt::Incomplete { statement, .. }
+                if matches!(&**statement, Stmt::Class { methods, .. } if methods.len() == 1)
+        ));
+        assert!(matches!(statements[2], Stmt::Var { .. }));
+
+        let messages: Vec<String> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "[line 2] Error at 'print': Expect '{' before function body.",
+                "[line 5] Error at 'm': Expect '{' before class body."
+            ]
+        );
     }
 }
@@ -0,0 +1,88 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+// Time spent in the functions of a program, collected in `--profile` mode
+#[derive(Default)]
+pub struct Profiler {
+    // Calls currently being evaluated, innermost last
+    frames: Vec<Frame>,
+    entries: HashMap<String, Entry>,
+}
+
+struct Frame {
+    name: String,
+    started: Instant,
+    // Time spent in the calls made by this one
+    children: Duration,
+}
+
+#[derive(Default)]
+struct Entry {
+    calls: usize,
+    // Includes the time of nested calls, recursive calls are only counted once
+    total: Duration,
+    // Excludes the time of nested calls
+    own: Duration,
+    active: usize,
+}
+
+impl Profiler {
+    pub fn enter(&mut self, name: String) {
+        let entry = self.entries.entry(name.clone()).or_default();
+        entry.calls += 1;
+        entry.active += 1;
+
+        self.frames.push(Frame {
+            name,
+            started: Instant::now(),
+            children: Duration::ZERO,
+        });
+    }
+
+    pub fn exit(&mut self) {
+        let frame = self.frames.pop().expect("Every exit follows an enter");
+        let elapsed = frame.started.elapsed();
+
+        let entry = self
+            .entries
+            .get_mut(&frame.name)
+            .expect("Entry was created on enter");
+        entry.active -= 1;
+        entry.own += elapsed.saturating_sub(frame.children);
+        if entry.active == 0 {
+            entry.total += elapsed;
+        }
+
+        if let Some(caller) = self.frames.last_mut() {
+            caller.children += elapsed;
+        }
+    }
+
+    // One line per function, the most expensive first
+    pub fn report(&self) -> String {
+        let mut entries: Vec<(&String, &Entry)> = self.entries.iter().collect();
+        entries.sort_by(|(l_name, l_entry), (r_name, r_entry)| {
+            r_entry
+                .total
+                .cmp(&l_entry.total)
+                .then_with(|| l_name.cmp(r_name))
+        });
+
+        let mut report = format!(
+            "{:<24} {:>8} {:>12} {:>12}\n",
+            "function", "calls", "total ms", "self ms"
+        );
+        for (name, entry) in entries {
+            report.push_str(&format!(
+                "{:<24} {:>8} {:>12.3} {:>12.3}\n",
+                name,
+                entry.calls,
+                entry.total.as_secs_f64() * 1000.0,
+                entry.own.as_secs_f64() * 1000.0
+            ));
+        }
+        report
+    }
+}
@@ -1,6 +1,14 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use crate::{error, expr::Expr, stmt::Stmt, token::Token};
+use crate::{
+    error,
+    error::Phase,
+    expr::Expr,
+    interpreter::InterpreterConfig,
+    stmt::Stmt,
+    symbol::Symbol,
+    token::{Span, Token},
+};
 
 #[derive(PartialEq)]
 enum FunctionType {
@@ -14,13 +22,26 @@ enum FunctionType {
 enum ClassType {
     None,
     Class,
-    Subclass,
 }
 
 pub struct Resolver {
-    scopes: Vec<HashMap<String, bool>>,
+    scopes: Vec<HashMap<Symbol, bool>>,
     current_function: FunctionType,
     current_class: ClassType,
+    // Loops and switches around the current statement, within the current function
+    loops: usize,
+    switches: usize,
+    warn_shadowing: bool,
+    global_this: bool,
+    factory_initializers: bool,
+    // Every variable reference with the distance to its scope (None for globals), if recording
+    resolutions: Option<Vec<(Span, Option<usize>)>>,
+}
+
+impl Default for Resolver {
+    fn default() -> Resolver {
+        Resolver::new()
+    }
 }
 
 impl Resolver {
@@ -29,9 +50,42 @@ impl Resolver {
             scopes: vec![],
             current_function: FunctionType::None,
             current_class: ClassType::None,
+            loops: 0,
+            switches: 0,
+            warn_shadowing: true,
+            global_this: false,
+            factory_initializers: false,
+            resolutions: None,
+        }
+    }
+
+    // Don't warn about locals shadowing variables of enclosing scopes
+    pub fn without_shadowing_warnings(mut self) -> Resolver {
+        self.warn_shadowing = false;
+        self
+    }
+
+    // Allow what the modes of the config allow, like `this` outside of classes for `global_this`
+    // or `init` returning a value for `factory_initializers`
+    pub fn for_config(config: &InterpreterConfig) -> Resolver {
+        Resolver {
+            global_this: config.global_this,
+            factory_initializers: config.factory_initializers,
+            ..Resolver::new()
         }
     }
 
+    // Also record how every variable reference was resolved, e.g. for "go to definition"
+    pub fn recording_resolutions(mut self) -> Resolver {
+        self.resolutions = Some(vec![]);
+        self
+    }
+
+    // The recorded resolutions in the order they were resolved, empty if not recording
+    pub fn resolutions(&self) -> &[(Span, Option<usize>)] {
+        self.resolutions.as_deref().unwrap_or_default()
+    }
+
     fn begin_scope(&mut self) {
         self.scopes.push(HashMap::new());
     }
@@ -44,13 +98,13 @@ impl Resolver {
         self.scopes.is_empty()
     }
 
-    fn peek_mut(&mut self) -> &mut HashMap<String, bool> {
+    fn peek_mut(&mut self) -> &mut HashMap<Symbol, bool> {
         self.scopes
             .last_mut()
             .expect("Scope stack was checked to be non-empty")
     }
 
-    fn peek(&self) -> &HashMap<String, bool> {
+    fn peek(&self) -> &HashMap<Symbol, bool> {
         self.scopes
             .last()
             .expect("Scope stack was checked to be non-empty")
@@ -61,13 +115,26 @@ impl Resolver {
             return;
         }
 
-        let scope = self.peek_mut();
+        let (scope, enclosing) = self
+            .scopes
+            .split_last_mut()
+            .expect("Scope stack was checked to be non-empty");
 
-        if scope.contains_key(&name.lexeme) {
-            error::error_token(name, "Already a variable with this name in this scope.");
+        if scope.contains_key(&name.lexeme.symbol()) {
+            error::error_token(
+                Phase::Resolve,
+                name,
+                "Already a variable with this name in this scope.",
+            );
+        } else if self.warn_shadowing
+            && enclosing
+                .iter()
+                .any(|outer| outer.contains_key(&name.lexeme.symbol()))
+        {
+            error::warning_token(name, "Shadows a variable from an enclosing scope.");
         }
 
-        scope.insert(name.lexeme.to_owned(), false);
+        scope.insert(name.lexeme.symbol(), false);
     }
 
     fn define(&mut self, name: &Token) {
@@ -75,12 +142,20 @@ impl Resolver {
             return;
         }
 
-        self.peek_mut().insert(name.lexeme.to_owned(), true);
+        self.peek_mut().insert(name.lexeme.symbol(), true);
     }
 
     fn resolve_local(&mut self, name: &Token) -> Option<usize> {
+        let depth = self.depth_of(name.lexeme.symbol());
+        if let Some(resolutions) = &mut self.resolutions {
+            resolutions.push((name.span(), depth));
+        }
+        depth
+    }
+
+    fn depth_of(&self, name: Symbol) -> Option<usize> {
         for (i, scope) in self.scopes.iter().rev().enumerate() {
-            if scope.contains_key(&name.lexeme) {
+            if scope.contains_key(&name) {
                 return Some(i);
             }
         }
@@ -94,6 +169,9 @@ impl Resolver {
         function_type: FunctionType,
     ) {
         let enclosing_function = std::mem::replace(&mut self.current_function, function_type);
+        // A break or continue can't reach a loop outside of the function
+        let enclosing_loops = std::mem::take(&mut self.loops);
+        let enclosing_switches = std::mem::take(&mut self.switches);
 
         self.begin_scope();
         for param in params {
@@ -104,6 +182,8 @@ impl Resolver {
         self.end_scope();
 
         self.current_function = enclosing_function;
+        self.loops = enclosing_loops;
+        self.switches = enclosing_switches;
     }
 }
 
@@ -115,11 +195,19 @@ pub trait Resolvable<T> {
 impl Resolvable<()> for &mut Stmt {
     fn resolve(self, resolver: &mut Resolver) {
         match self {
+            Stmt::Incomplete {
+                missing: _,
+                statement,
+            } => statement.resolve(resolver),
             Stmt::Block { statements } => {
                 resolver.begin_scope();
                 statements.resolve(resolver);
                 resolver.end_scope();
             }
+            Stmt::Enum { name, variants: _ } => {
+                resolver.declare(name);
+                resolver.define(name);
+            }
             Stmt::Var { name, initializer } => {
                 resolver.declare(name);
                 if let Some(initializer) = initializer {
@@ -127,11 +215,23 @@ impl Resolvable<()> for &mut Stmt {
                 }
                 resolver.define(name);
             }
-            Stmt::Function { name, params, body } => {
-                resolver.declare(name);
-                resolver.define(name);
-
-                // resolveFunction
+            Stmt::VarDestructure {
+                bracket: _,
+                names,
+                initializer,
+            } => {
+                initializer.resolve(resolver);
+                for name in names {
+                    resolver.declare(name);
+                    resolver.define(name);
+                }
+            }
+            // The name was already declared together with the rest of the block
+            Stmt::Function {
+                name: _,
+                params,
+                body,
+            } => {
                 resolver.resolve_function(params, body, FunctionType::Function);
             }
             Stmt::Expression { expr } => {
@@ -148,29 +248,136 @@ impl Resolvable<()> for &mut Stmt {
                     else_branch.resolve(resolver);
                 }
             }
-            Stmt::Print { expr } => {
+            Stmt::Print { expr } | Stmt::Eprint { expr } => {
                 expr.resolve(resolver);
             }
+            Stmt::Assert {
+                keyword: _,
+                condition,
+                message,
+            } => {
+                condition.resolve(resolver);
+                if let Some(message) = message {
+                    message.resolve(resolver);
+                }
+            }
             Stmt::Return { keyword, value } => {
                 if resolver.current_function == FunctionType::None {
-                    error::error_token(keyword, "Can't return from top-level code.");
+                    error::error_token(
+                        Phase::Resolve,
+                        keyword,
+                        "Can't return from top-level code.",
+                    );
                 }
 
                 if let Some(value) = value {
-                    if resolver.current_function == FunctionType::Initializer {
-                        error::error_token(keyword, "Can't return a value from an initializer.");
+                    if resolver.current_function == FunctionType::Initializer
+                        && !resolver.factory_initializers
+                    {
+                        error::error_token(
+                            Phase::Resolve,
+                            keyword,
+                            "Can't return a value from an initializer.",
+                        );
                     }
 
                     value.resolve(resolver);
                 }
             }
-            Stmt::While { condition, body } => {
+            Stmt::While {
+                condition,
+                body,
+                increment,
+                scope_per_iteration: _,
+            } => {
                 condition.resolve(resolver);
+                resolver.loops += 1;
+                body.resolve(resolver);
+                resolver.loops -= 1;
+                if let Some(increment) = increment {
+                    increment.resolve(resolver);
+                }
+            }
+            Stmt::ForIn {
+                name,
+                keyword: _,
+                iterable,
+                body,
+            } => {
+                iterable.resolve(resolver);
+                resolver.begin_scope();
+                resolver.declare(name);
+                resolver.define(name);
+                resolver.loops += 1;
                 body.resolve(resolver);
+                resolver.loops -= 1;
+                resolver.end_scope();
+            }
+            Stmt::Throw { keyword: _, value } => value.resolve(resolver),
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                resolver.begin_scope();
+                body.resolve(resolver);
+                resolver.end_scope();
+                if let Some((name, handler)) = catch {
+                    resolver.begin_scope();
+                    resolver.declare(name);
+                    resolver.define(name);
+                    handler.resolve(resolver);
+                    resolver.end_scope();
+                }
+                if let Some(finally) = finally {
+                    resolver.begin_scope();
+                    finally.resolve(resolver);
+                    resolver.end_scope();
+                }
+            }
+            Stmt::Break { keyword } => {
+                if resolver.loops == 0 && resolver.switches == 0 {
+                    error::error_token(
+                        Phase::Resolve,
+                        keyword,
+                        "Can't use 'break' outside of a loop or switch.",
+                    );
+                }
+            }
+            Stmt::Continue { keyword } => {
+                if resolver.loops == 0 {
+                    error::error_token(
+                        Phase::Resolve,
+                        keyword,
+                        "Can't use 'continue' outside of a loop.",
+                    );
+                }
+            }
+            Stmt::Switch {
+                keyword: _,
+                value,
+                cases,
+                default,
+            } => {
+                value.resolve(resolver);
+                resolver.switches += 1;
+                for (case, body) in cases {
+                    case.resolve(resolver);
+                    resolver.begin_scope();
+                    body.resolve(resolver);
+                    resolver.end_scope();
+                }
+                if let Some(body) = default {
+                    resolver.begin_scope();
+                    body.resolve(resolver);
+                    resolver.end_scope();
+                }
+                resolver.switches -= 1;
             }
             Stmt::Class {
                 name,
                 superclass,
+                mixins,
                 methods,
             } => {
                 let enclosing_class =
@@ -187,6 +394,7 @@ impl Resolvable<()> for &mut Stmt {
                         } => {
                             if superclass_name.lexeme == name.lexeme {
                                 error::error_token(
+                                    Phase::Resolve,
                                     superclass_name,
                                     "A class can't inherit from itself.",
                                 );
@@ -195,18 +403,32 @@ impl Resolvable<()> for &mut Stmt {
                         _ => unreachable!("Superclass Expression should always be a variable"),
                     }
 
-                    resolver.current_class = ClassType::Subclass;
-
                     superclass.resolve(resolver);
                 }
 
-                if superclass.is_some() {
-                    resolver.begin_scope();
-                    resolver.peek_mut().insert("super".to_string(), true);
+                for mixin in mixins {
+                    if let Expr::Variable {
+                        name: mixin_name, ..
+                    } = mixin
+                    {
+                        if mixin_name.lexeme == name.lexeme {
+                            error::error_token(
+                                Phase::Resolve,
+                                mixin_name,
+                                "A class can't mix in itself.",
+                            );
+                        }
+                    }
+                    mixin.resolve(resolver);
                 }
 
+                // Every class binds `super`, as its methods refer to the superclass of whichever
+                // class mixes it in, see `rebind_super` in the interpreter
+                resolver.begin_scope();
+                resolver.peek_mut().insert(Symbol::SUPER, true);
+
                 resolver.begin_scope();
-                resolver.peek_mut().insert("this".to_string(), true);
+                resolver.peek_mut().insert(Symbol::THIS, true);
 
                 for method in methods {
                     match method {
@@ -217,15 +439,17 @@ impl Resolvable<()> for &mut Stmt {
                             };
                             resolver.resolve_function(params, body, function_type);
                         }
-                        _ => panic!("Class can only contain methods."),
+                        // The parser only produces methods, but don't trust a hand built AST
+                        _ => error::error_token(
+                            Phase::Resolve,
+                            name,
+                            "Class can only contain methods.",
+                        ),
                     }
                 }
 
                 resolver.end_scope();
-
-                if superclass.is_some() {
-                    resolver.end_scope();
-                }
+                resolver.end_scope();
 
                 resolver.current_class = enclosing_class;
             }
@@ -233,8 +457,41 @@ impl Resolvable<()> for &mut Stmt {
     }
 }
 
+// Properties starting with `_` are private to the instance: only `this._x` reaches them, any
+// other object is an error, even another instance of the same class
+fn check_private(object: &Expr, name: &Token) {
+    if name.lexeme.as_str().starts_with('_') && !matches!(object, Expr::This { .. }) {
+        error::error_token(
+            Phase::Resolve,
+            name,
+            &format!(
+                "Can only access private property '{}' through 'this'.",
+                name.lexeme
+            ),
+        );
+    }
+}
+
+// The name of a function declaration, also if it's incomplete, as it's declared all the same
+fn hoisted_function(statement: &Stmt) -> Option<&Token> {
+    match statement {
+        Stmt::Function { name, .. } => Some(name.as_ref()),
+        Stmt::Incomplete { statement, .. } => hoisted_function(statement),
+        _ => None,
+    }
+}
+
 impl Resolvable<()> for &mut Vec<Stmt> {
     fn resolve(self, resolver: &mut Resolver) {
+        // Functions are declared for their whole block up front, like the interpreter hoists
+        // them, so they can refer to each other regardless of order, e.g. for mutual recursion
+        for statement in self.iter() {
+            if let Some(name) = hoisted_function(statement) {
+                resolver.declare(name);
+                resolver.define(name);
+            }
+        }
+
         for statement in self {
             statement.resolve(resolver);
         }
@@ -245,8 +502,14 @@ impl Resolvable<()> for &mut Expr {
     fn resolve(self, resolver: &mut Resolver) {
         match self {
             Expr::Variable { name, depth } => {
-                if !resolver.is_empty() && resolver.peek().get(&name.lexeme) == Some(&false) {
-                    error::error_token(name, "Can't read local variable in its own initializer.");
+                if !resolver.is_empty()
+                    && resolver.peek().get(&name.lexeme.symbol()) == Some(&false)
+                {
+                    error::error_token(
+                        Phase::Resolve,
+                        name,
+                        "Can't read local variable in its own initializer.",
+                    );
                 }
 
                 *depth = resolver.resolve_local(name);
@@ -273,6 +536,18 @@ impl Resolvable<()> for &mut Expr {
                     argument.resolve(resolver);
                 }
             }
+            Expr::Cascade {
+                object,
+                name,
+                closing_paren: _,
+                arguments,
+            } => {
+                check_private(object, name);
+                object.resolve(resolver);
+                for argument in arguments {
+                    argument.resolve(resolver);
+                }
+            }
             Expr::Grouping { expression } => {
                 expression.resolve(resolver);
             }
@@ -297,48 +572,193 @@ impl Resolvable<()> for &mut Expr {
                 then.resolve(resolver);
                 r#else.resolve(resolver);
             }
-            Expr::Get { object, name: _ } => {
+            Expr::List { elements } => {
+                for element in elements {
+                    element.resolve(resolver);
+                }
+            }
+            Expr::Map { entries } => {
+                for (key, value) in entries {
+                    key.resolve(resolver);
+                    value.resolve(resolver);
+                }
+            }
+            Expr::Function { params, body } => {
+                resolver.resolve_function(params, body, FunctionType::Function);
+            }
+            Expr::Get { object, name } | Expr::Delete { object, name } => {
+                check_private(object, name);
                 object.resolve(resolver);
             }
             Expr::Set {
                 object,
-                name: _,
+                name,
                 value,
+                operator: _,
             } => {
+                check_private(object, name);
                 value.resolve(resolver);
                 object.resolve(resolver);
             }
+            Expr::Index {
+                object,
+                bracket: _,
+                index,
+            } => {
+                object.resolve(resolver);
+                index.resolve(resolver);
+            }
+            Expr::IndexSet {
+                object,
+                bracket: _,
+                index,
+                value,
+                operator: _,
+            } => {
+                object.resolve(resolver);
+                index.resolve(resolver);
+                value.resolve(resolver);
+            }
             Expr::This { keyword, depth } => {
-                if resolver.current_class == ClassType::None {
-                    error::error_token(keyword, "Can't use 'this' outside of a class.");
+                if resolver.current_class == ClassType::None && !resolver.global_this {
+                    error::error_token(
+                        Phase::Resolve,
+                        keyword,
+                        "Can't use 'this' outside of a class.",
+                    );
                     return;
                 }
 
-                *depth = resolver
-                    .resolve_local(keyword)
-                    .expect("This must exist as it can only be used in classes");
+                *depth = resolver.resolve_local(keyword);
             }
             Expr::Super {
                 keyword,
                 method: _,
                 depth,
+                this_depth,
             } => {
+                // Without a superclass, it's an error once it's run, unless a class with one mixed
+                // in the method
                 match resolver.current_class {
                     ClassType::None => {
-                        error::error_token(keyword, "Can't use 'super' outside of a class.");
-                    }
-                    ClassType::Class => {
                         error::error_token(
+                            Phase::Resolve,
                             keyword,
-                            "Can't use 'super' in a class with no superclass.",
+                            "Can't use 'super' outside of a class.",
                         );
                     }
-                    // All good
-                    ClassType::Subclass => {
-                        *depth = resolver.resolve_local(keyword).expect("Super must exist");
+                    ClassType::Class => {
+                        *depth = resolver.resolve_local(keyword);
+                        *this_depth = resolver.depth_of(Symbol::THIS);
                     }
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Resolvable, Resolver};
+    use crate::{error, parser::Parser, scanner::Scanner, token::Span};
+
+    #[test]
+    fn test_recorded_resolutions() {
+        let source = "var global = 1;\n{\n  var local = 2;\n  print local + global;\n}";
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let mut statements = Parser::new(tokens).parse().expect("Source should parse");
+
+        let mut resolver = Resolver::new().recording_resolutions();
+        statements.resolve(&mut resolver);
+
+        let local = Span {
+            line: 4,
+            column: 9,
+            length: 5,
+        };
+        let global = Span {
+            line: 4,
+            column: 17,
+            length: 6,
+        };
+        assert_eq!(resolver.resolutions(), [(local, Some(0)), (global, None)]);
+    }
+
+    #[test]
+    fn test_resolutions_are_not_recorded_by_default() {
+        let tokens = Scanner::new("{ var a = 1; print a; }".to_string()).scan_tokens();
+        let mut statements = Parser::new(tokens).parse().expect("Source should parse");
+
+        let mut resolver = Resolver::new();
+        statements.resolve(&mut resolver);
+
+        assert!(resolver.resolutions().is_empty());
+    }
+
+    #[test]
+    fn test_resolving_twice_gives_the_same_depths() {
+        let source = "var a = 1;\n\
+            fun counter() {\n  var count = 0;\n  fun increment() { count = count + a; return count; }\n  return increment;\n}\n\
+            class A { method() { return this; } }\n\
+            class B < A { method() { var m = super.method(); return m; } }\n\
+            for (var x in [1, 2]) { var y = x; print y; }";
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let mut statements = Parser::new(tokens).parse().expect("Source should parse");
+
+        let mut resolver = Resolver::new().recording_resolutions();
+        let (_, first) = error::collect_diagnostics(|| statements.resolve(&mut resolver));
+        let resolved = format!("{:?}", statements);
+        let (_, second) = error::collect_diagnostics(|| statements.resolve(&mut resolver));
+
+        assert!(first.is_empty());
+        assert!(second.is_empty(), "{:?}", second);
+        assert_eq!(format!("{:?}", statements), resolved);
+        let resolutions = resolver.resolutions();
+        let (first, second) = resolutions.split_at(resolutions.len() / 2);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_private_properties_are_only_reachable_through_this() {
+        let source = "class A {\n  init() { this._x = 1; }\n  x() { return this._x + this._m(); }\n  _m() { return 1; }\n  peek(other) { return other._x; }\n}\n\
+            var a = A();\na._x = 2;\nprint a._m();";
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let mut statements = Parser::new(tokens).parse().expect("Source should parse");
+
+        let (_, diagnostics) =
+            error::collect_diagnostics(|| statements.resolve(&mut Resolver::new()));
+        let messages: Vec<String> = diagnostics
+            .iter()
+            .map(|diagnostic| format!("{}: {}", diagnostic.line, diagnostic.message))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "5: Can only access private property '_x' through 'this'.",
+                "8: Can only access private property '_x' through 'this'.",
+                "9: Can only access private property '_m' through 'this'."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_incomplete_functions_are_hoisted_too() {
+        let source = "{\n  print later();\n  fun later()\n    return 1;\n  }\n}";
+        let (statements, _) = error::collect_diagnostics(|| {
+            let tokens = Scanner::new(source.to_string()).scan_tokens();
+            Parser::new(tokens).with_recovery().parse()
+        });
+        let mut statements = statements.expect("Recovery returns the statements");
+
+        let mut resolver = Resolver::new().recording_resolutions();
+        let (_, diagnostics) = error::collect_diagnostics(|| statements.resolve(&mut resolver));
+
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        let later = Span {
+            line: 2,
+            column: 9,
+            length: 5,
+        };
+        assert_eq!(resolver.resolutions(), [(later, Some(0))]);
+    }
+}
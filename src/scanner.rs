@@ -1,39 +1,141 @@
-use crate::{error, token::Token, token_type::TokenType};
+use std::io::BufRead;
+
+use crate::{
+    error,
+    token::{Lexeme, Token},
+    token_type::TokenType,
+};
+
+// Tabs advance the column to the next multiple of this, like in most terminals
+const DEFAULT_TAB_WIDTH: usize = 8;
+
+// Longer literals are reported instead of scanned, so untrusted sources can't make tokens of any
+// size. No handwritten code comes close to these.
+const DEFAULT_MAX_IDENTIFIER_LENGTH: usize = 4096;
+const DEFAULT_MAX_STRING_LENGTH: usize = 1 << 24;
+// "continue", see `keyword`
+const LONGEST_KEYWORD: usize = 8;
 
-#[derive(Debug)]
 pub struct Scanner {
-    source: String,
+    // The whole source, or only the part from the current token on when reading from `reader`
     source_chars: Vec<char>,
+    // More of the source, read a line at a time, see `from_reader`
+    reader: Option<Box<dyn BufRead>>,
     tokens: Vec<Token>,
     start: usize,
     current: usize,
     line: usize,
+    // Emit comments as tokens, e.g. for formatters, instead of discarding them
+    preserve_comments: bool,
+    // Scan `print` as an identifier, see `InterpreterConfig::print_function`
+    print_keyword: bool,
+    tab_width: usize,
+    // In chars, see `with_max_identifier_length` and `with_max_string_length`
+    max_identifier_length: usize,
+    max_string_length: usize,
+    // The column after the last consumed char, and the one the current token starts at
+    column: usize,
+    start_column: usize,
 }
 
 impl Scanner {
     pub fn new(source: String) -> Self {
+        // Editors on Windows like to start files with a byte order mark, it's not part of the code
+        let source = source.strip_prefix('\u{FEFF}').unwrap_or(&source);
+
         Scanner {
             source_chars: source.chars().collect(),
-            source,
+            reader: None,
             tokens: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
+            preserve_comments: false,
+            print_keyword: true,
+            tab_width: DEFAULT_TAB_WIDTH,
+            max_identifier_length: DEFAULT_MAX_IDENTIFIER_LENGTH,
+            max_string_length: DEFAULT_MAX_STRING_LENGTH,
+            column: 1,
+            start_column: 1,
         }
     }
 
+    // Scans the source as it's read, so large files never have to be in memory as a whole.
+    // Only the tokens are kept, not the text they came from.
+    pub fn from_reader(reader: impl BufRead + 'static) -> Self {
+        let mut scanner = Scanner::new(String::new());
+        scanner.reader = Some(Box::new(reader));
+        scanner.fill();
+        if scanner.source_chars.first() == Some(&'\u{FEFF}') {
+            scanner.source_chars.remove(0);
+            scanner.fill();
+        }
+        scanner
+    }
+
+    pub fn preserving_comments(mut self) -> Self {
+        self.preserve_comments = true;
+        self
+    }
+
+    pub fn without_print_keyword(mut self) -> Self {
+        self.print_keyword = false;
+        self
+    }
+
+    // A tab width of 1 counts tabs like any other character
+    pub fn with_tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width.max(1);
+        self
+    }
+
+    // Longer identifiers are an error, e.g. to limit what untrusted sources can allocate
+    pub fn with_max_identifier_length(mut self, max_length: usize) -> Self {
+        self.max_identifier_length = max_length;
+        self
+    }
+
+    // The same for the content of string literals, raw ones included
+    pub fn with_max_string_length(mut self, max_length: usize) -> Self {
+        self.max_string_length = max_length;
+        self
+    }
+
     pub fn scan_tokens(mut self) -> Vec<Token> {
         while !self.is_at_end() {
+            // What came before the next token isn't looked at again
+            if self.reader.is_some() {
+                self.source_chars.drain(..self.current);
+                self.current = 0;
+            }
             self.start = self.current;
+            self.start_column = self.column;
             self.scan_token();
         }
 
         self.tokens
-            .push(Token::new(TokenType::Eof, String::from(""), self.line));
+            .push(Token::new(TokenType::Eof, "", self.line).at_column(self.column));
 
         self.tokens
     }
 
+    // Reads lines until the char after the current one is available, or the reader is done.
+    // Whole lines are read, so lookahead within the line (like `closes_raw_string`) works too.
+    fn fill(&mut self) {
+        while self.source_chars.len() <= self.current + 1 {
+            let Some(reader) = self.reader.as_mut() else {
+                return;
+            };
+
+            let mut line = String::new();
+            let read = reader.read_line(&mut line).expect("Failed to read source");
+            if read == 0 {
+                self.reader = None;
+            }
+            self.source_chars.extend(line.chars());
+        }
+    }
+
     fn is_at_end(&self) -> bool {
         self.current >= self.source_chars.len()
     }
@@ -45,14 +147,60 @@ impl Scanner {
             '(' => self.add_token(TokenType::LeftParen),
             ')' => self.add_token(TokenType::RightParen),
             '{' => self.add_token(TokenType::LeftBrace),
+            '[' => self.add_token(TokenType::LeftBracket),
+            ']' => self.add_token(TokenType::RightBracket),
             '}' => self.add_token(TokenType::RightBrace),
             ',' => self.add_token(TokenType::Comma),
-            '.' => self.add_token(TokenType::Dot),
-            '-' => self.add_token(TokenType::Minus),
-            '+' => self.add_token(TokenType::Plus),
+            // A dot right before a digit starts a number like `.5`, otherwise it's member access
+            '.' if self.peek().is_ascii_digit() => self.number(),
+            '.' => {
+                if self.matches('.') {
+                    self.add_token(TokenType::DotDot)
+                } else {
+                    self.add_token(TokenType::Dot)
+                }
+            }
+            '-' => {
+                if self.matches('=') {
+                    self.add_token(TokenType::MinusEqual)
+                } else {
+                    self.add_token(TokenType::Minus)
+                }
+            }
+            '+' => {
+                if self.matches('=') {
+                    self.add_token(TokenType::PlusEqual)
+                } else {
+                    self.add_token(TokenType::Plus)
+                }
+            }
             ';' => self.add_token(TokenType::Semicolon),
-            '*' => self.add_token(TokenType::Star),
-            '?' => self.add_token(TokenType::QuestionMark),
+            // Lox spells these operators `or` and `and`, so there is no `||` or `&&` on their own
+            '|' if self.peek() == '|' && self.peek_next() == '=' => {
+                self.advance();
+                self.advance();
+                self.add_token(TokenType::PipePipeEqual)
+            }
+            '&' if self.peek() == '&' && self.peek_next() == '=' => {
+                self.advance();
+                self.advance();
+                self.add_token(TokenType::AmpAmpEqual)
+            }
+            '*' => {
+                if self.matches('=') {
+                    self.add_token(TokenType::StarEqual)
+                } else {
+                    self.add_token(TokenType::Star)
+                }
+            }
+            '?' => {
+                // A ternary's then branch can't start with ':', so this is always the Elvis operator
+                if self.matches(':') {
+                    self.add_token(TokenType::QuestionColon)
+                } else {
+                    self.add_token(TokenType::QuestionMark)
+                }
+            }
             ':' => self.add_token(TokenType::Colon),
 
             '!' => {
@@ -65,6 +213,8 @@ impl Scanner {
             '=' => {
                 if self.matches('=') {
                     self.add_token(TokenType::EqualEqual)
+                } else if self.matches('>') {
+                    self.add_token(TokenType::Arrow)
                 } else {
                     self.add_token(TokenType::Equal)
                 }
@@ -72,6 +222,8 @@ impl Scanner {
             '<' => {
                 if self.matches('=') {
                     self.add_token(TokenType::LessEqual)
+                } else if self.matches('<') {
+                    self.add_token(TokenType::LessLess)
                 } else {
                     self.add_token(TokenType::Less)
                 }
@@ -79,6 +231,13 @@ impl Scanner {
             '>' => {
                 if self.matches('=') {
                     self.add_token(TokenType::GreaterEqual)
+                } else if self.matches('>') {
+                    // Longest match, so `>>>` is never a `>>` followed by a `>`
+                    if self.matches('>') {
+                        self.add_token(TokenType::GreaterGreaterGreater)
+                    } else {
+                        self.add_token(TokenType::GreaterGreater)
+                    }
                 } else {
                     self.add_token(TokenType::Greater)
                 }
@@ -90,6 +249,15 @@ impl Scanner {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+
+                    if self.preserve_comments {
+                        let text = self.substring(self.start + 2, self.current);
+                        self.add_token(TokenType::Comment(text));
+                    }
+                } else if self.matches('*') {
+                    self.block_comment();
+                } else if self.matches('=') {
+                    self.add_token(TokenType::SlashEqual);
                 } else {
                     self.add_token(TokenType::Slash);
                 }
@@ -100,8 +268,13 @@ impl Scanner {
 
             '"' => self.string(),
 
+            '0' if self.peek() == 'x' => self.prefixed_number(16, "hexadecimal"),
+            '0' if self.peek() == 'o' => self.prefixed_number(8, "octal"),
+            '0' if self.peek() == 'b' => self.prefixed_number(2, "binary"),
             '0'..='9' => self.number(),
 
+            'r' if matches!(self.peek(), '"' | '#') => self.raw_string(),
+
             c if c.is_alpha_lox() => self.identifier(),
 
             c => {
@@ -114,67 +287,201 @@ impl Scanner {
     }
 
     fn identifier(&mut self) {
+        // Keywords aren't held to the limit, but no name longer than all of them is one
+        let limit = self.max_identifier_length.max(LONGEST_KEYWORD);
+        let mut too_long = false;
         while self.peek().is_alphanumeric_lox() {
             self.advance();
+            if too_long {
+                self.forget_consumed();
+            } else {
+                too_long = self.current - self.start > limit;
+            }
         }
 
-        let text = self.source[self.start..self.current].to_string();
+        if !too_long {
+            let text = self.substring(self.start, self.current);
+            match self.keyword(&text) {
+                Some(token_type) => return self.add_token(token_type),
+                None if self.current - self.start > self.max_identifier_length => {}
+                None => return self.add_token(TokenType::Identifier),
+            }
+        }
 
-        match self.keyword(&text) {
-            Some(token_type) => self.add_token(token_type),
-            None => self.add_token(TokenType::Identifier),
-        };
+        let message = format!(
+            "Identifier is longer than the limit of {} characters.",
+            self.max_identifier_length
+        );
+        error::error(self.line, &message);
     }
 
     fn keyword(&mut self, text: &str) -> Option<TokenType> {
         match text {
             "and" => Some(TokenType::And),
+            "assert" => Some(TokenType::Assert),
+            "break" => Some(TokenType::Break),
+            "case" => Some(TokenType::Case),
+            "catch" => Some(TokenType::Catch),
             "class" => Some(TokenType::Class),
+            "continue" => Some(TokenType::Continue),
+            "default" => Some(TokenType::Default),
+            "delete" => Some(TokenType::Delete),
             "else" => Some(TokenType::Else),
+            "enum" => Some(TokenType::Enum),
+            "eprint" => Some(TokenType::Eprint),
             "false" => Some(TokenType::False),
+            "finally" => Some(TokenType::Finally),
             "for" => Some(TokenType::For),
             "fun" => Some(TokenType::Fun),
             "if" => Some(TokenType::If),
+            "in" => Some(TokenType::In),
+            "is" => Some(TokenType::Is),
+            "loop" => Some(TokenType::Loop),
             "nil" => Some(TokenType::Nil),
             "or" => Some(TokenType::Or),
-            "print" => Some(TokenType::Print),
+            "print" if self.print_keyword => Some(TokenType::Print),
             "return" => Some(TokenType::Return),
             "super" => Some(TokenType::Super),
+            "switch" => Some(TokenType::Switch),
             "this" => Some(TokenType::This),
+            "throw" => Some(TokenType::Throw),
             "true" => Some(TokenType::True),
+            "try" => Some(TokenType::Try),
             "var" => Some(TokenType::Var),
             "while" => Some(TokenType::While),
+            "with" => Some(TokenType::With),
             _ => None,
         }
     }
 
     fn number(&mut self) {
-        while self.peek().is_ascii_digit() {
+        self.digits();
+
+        // Look for a fractional part, unless the number started with it.
+        let leading_dot = self.source_chars[self.start] == '.';
+        if !leading_dot && self.peek() == '.' && self.peek_next().is_ascii_digit() {
+            // Consume the "."
             self.advance();
+
+            self.digits();
         }
 
-        // Look for a fractional part.
-        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
-            // Consume the "."
+        let literal = &self.source_chars[self.start..self.current];
+        if let Some(message) = separator_error(literal) {
+            error::error(self.line, message);
+            return;
+        }
+
+        // `010` would be octal in C but decimal here, so it's rejected as ambiguous
+        let second = literal.get(1);
+        if literal[0] == '0' && second.is_some_and(|c| c.is_ascii_digit() || *c == '_') {
+            error::error(
+                self.line,
+                "Leading zeros are not allowed; use 0o prefix for octal.",
+            );
+            return;
+        }
+
+        let lexeme: String = literal.iter().filter(|c| **c != '_').collect();
+
+        match lexeme.parse::<f64>() {
+            // Literals too large for a f64 would silently become infinity
+            Ok(value) if value.is_finite() => self.add_token(TokenType::Number(value)),
+            _ => error::error(self.line, "Invalid number literal."),
+        }
+    }
+
+    // Decimal digits, which can be grouped with separators like `1_000`
+    fn digits(&mut self) {
+        while self.peek().is_ascii_digit() || self.peek() == '_' {
+            self.advance();
+        }
+    }
+
+    // An integer like `0xFF`, `0o17` or `0b1010`, the "0" is consumed already
+    fn prefixed_number(&mut self, radix: u32, base: &str) {
+        // Consume the base prefix
+        self.advance();
+
+        // Everything up to the end of the word belongs to the literal, e.g. the "9" of `0o19`
+        while self.peek().is_alphanumeric_lox() {
             self.advance();
+        }
 
-            while self.peek().is_ascii_digit() {
-                self.advance();
+        let digits = &self.source_chars[self.start + 2..self.current];
+        if digits.first() == Some(&'_') {
+            error::error(
+                self.line,
+                "Digit separator '_' can't follow the base prefix.",
+            );
+            return;
+        }
+        if let Some(message) = separator_error(digits) {
+            error::error(self.line, message);
+            return;
+        }
+
+        let value =
+            match digits.is_empty() {
+                true => None,
+                false => digits.iter().filter(|digit| **digit != '_').try_fold(
+                    0.0,
+                    |value: f64, digit| {
+                        digit
+                            .to_digit(radix)
+                            .map(|digit| value * radix as f64 + digit as f64)
+                    },
+                ),
+            };
+
+        match value {
+            Some(value) if value.is_finite() => self.add_token(TokenType::Number(value)),
+            _ => error::error(self.line, format!("Invalid {} literal.", base).as_str()),
+        }
+    }
+
+    fn block_comment(&mut self) {
+        let start_line = self.line;
+
+        while !(self.is_at_end() || self.peek() == '*' && self.peek_next() == '/') {
+            if self.peek() == '\n' {
+                self.line += 1;
             }
+            self.advance();
         }
 
-        self.add_token(TokenType::Number(
-            self.source[self.start..self.current].parse().unwrap(),
-        ));
+        if self.is_at_end() {
+            error::error(start_line, "Unterminated block comment.");
+            return;
+        }
+
+        // The closing "*/"
+        self.advance();
+        self.advance();
+
+        if self.preserve_comments {
+            let text = self.substring(self.start + 2, self.current - 2);
+            let lexeme = Lexeme::Text(self.substring(self.start, self.current).into());
+            self.tokens.push(
+                Token::new(TokenType::Comment(text), lexeme, start_line)
+                    .at_column(self.start_column),
+            );
+        }
     }
 
     fn string(&mut self) {
+        let mut too_long = false;
         while self.peek() != '"' && !self.is_at_end() {
             if self.peek() == '\n' {
                 self.line += 1;
             }
 
             self.advance();
+            if too_long {
+                self.forget_consumed();
+            } else {
+                too_long = self.string_too_long(self.current - self.start - 1);
+            }
         }
 
         if self.is_at_end() {
@@ -185,11 +492,88 @@ impl Scanner {
         // The closing ".
         self.advance();
 
+        if too_long {
+            return;
+        }
+
         // Trim the surrounding quotes.
-        let value = self.source[self.start + 1..self.current - 1].to_string();
+        let value = self.substring(self.start + 1, self.current - 1);
         self.add_token(TokenType::String(value));
     }
 
+    // r"..." or r#"..."# with any number of #, which allows quotes inside the string
+    fn raw_string(&mut self) {
+        let mut hashes = 0;
+        while self.matches('#') {
+            hashes += 1;
+        }
+
+        if !self.matches('"') {
+            error::error(self.line, "Expect '\"' to start raw string.");
+            return;
+        }
+        let content_start = self.current;
+        let mut too_long = false;
+
+        loop {
+            if self.is_at_end() {
+                error::error(self.line, "Unterminated raw string.");
+                return;
+            }
+
+            let c = self.advance();
+            if c == '\n' {
+                self.line += 1;
+            } else if c == '"' && self.closes_raw_string(hashes) {
+                break;
+            }
+
+            if too_long {
+                self.forget_consumed();
+            } else {
+                too_long = self.string_too_long(self.current - content_start);
+            }
+        }
+
+        for _ in 0..hashes {
+            self.advance();
+        }
+        if too_long {
+            return;
+        }
+        let content_end = self.current - hashes - 1;
+
+        let value = self.substring(content_start, content_end);
+        self.add_token(TokenType::String(value));
+    }
+
+    fn string_too_long(&self, length: usize) -> bool {
+        let too_long = length > self.max_string_length;
+        if too_long {
+            let message = format!(
+                "String literal is longer than the limit of {} characters.",
+                self.max_string_length
+            );
+            error::error(self.line, &message);
+        }
+        too_long
+    }
+
+    // Once a literal is over its limit the rest of it is only skipped, so with a reader what was
+    // consumed of it is dropped instead of piling up until the literal ends
+    fn forget_consumed(&mut self) {
+        // Only once most of the buffer is consumed, so the chars left aren't moved for every one
+        if self.reader.is_some() && self.current > self.source_chars.len() / 2 {
+            self.source_chars.drain(..self.current);
+            self.current = 0;
+            self.start = 0;
+        }
+    }
+
+    fn closes_raw_string(&self, hashes: usize) -> bool {
+        (0..hashes).all(|i| self.source_chars.get(self.current + i) == Some(&'#'))
+    }
+
     fn matches(&mut self, expected: char) -> bool {
         if self.is_at_end() {
             return false;
@@ -199,7 +583,7 @@ impl Scanner {
             return false;
         }
 
-        self.current += 1;
+        self.advance();
         true
     }
 
@@ -222,12 +606,31 @@ impl Scanner {
     fn advance(&mut self) -> char {
         let c = self.source_chars[self.current];
         self.current += 1;
+        self.fill();
+        self.column = match c {
+            '\n' => 1,
+            // Tabs advance to the next tab stop, columns count from 1
+            '\t' => ((self.column - 1) / self.tab_width + 1) * self.tab_width + 1,
+            _ => self.column + 1,
+        };
         c
     }
 
+    // `start` and `current` count chars, so the source can't be sliced by them directly
+    fn substring(&self, start: usize, end: usize) -> String {
+        self.source_chars[start..end].iter().collect()
+    }
+
     fn add_token(&mut self, token_type: TokenType) {
-        let text = self.source[self.start..self.current].to_string();
-        self.tokens.push(Token::new(token_type, text, self.line));
+        let text = self.substring(self.start, self.current);
+        let lexeme = match token_type {
+            TokenType::String(_) | TokenType::Number(_) | TokenType::Comment(_) => {
+                Lexeme::Text(text.into())
+            }
+            _ => Lexeme::from(text.as_str()),
+        };
+        self.tokens
+            .push(Token::new(token_type, lexeme, self.line).at_column(self.start_column));
     }
 }
 
@@ -251,10 +654,21 @@ impl Alphanumeric for char {
     }
 }
 
+// Separators have to be between two digits, so `1__0`, `1_` and `1_.5` are errors
+fn separator_error(literal: &[char]) -> Option<&'static str> {
+    let is_digit = |c: Option<&char>| c.is_some_and(|c| c.is_ascii_alphanumeric());
+    let misplaced = (0..literal.len()).filter(|i| literal[*i] == '_').any(|i| {
+        !is_digit(i.checked_sub(1).and_then(|i| literal.get(i))) || !is_digit(literal.get(i + 1))
+    });
+
+    misplaced.then_some("Digit separator '_' must be between digits.")
+}
+
 #[cfg(test)]
 mod tests {
     use super::Scanner;
     use super::TokenType;
+    use crate::error;
 
     #[test]
     fn test_scanner_single_character_tokens() {
@@ -281,6 +695,196 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_scanner_compound_assignment_tokens() {
+        let tokens = Scanner::new(String::from("+= -= *= /= + - * /")).scan_tokens();
+
+        let token_types: Vec<TokenType> =
+            tokens.into_iter().map(|token| token.token_type).collect();
+        assert_eq!(
+            token_types,
+            [
+                TokenType::PlusEqual,
+                TokenType::MinusEqual,
+                TokenType::StarEqual,
+                TokenType::SlashEqual,
+                TokenType::Plus,
+                TokenType::Minus,
+                TokenType::Star,
+                TokenType::Slash,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scanner_shift_tokens() {
+        let tokens = Scanner::new(String::from("<< >> >>> >>>> >= <=")).scan_tokens();
+
+        let token_types: Vec<TokenType> =
+            tokens.into_iter().map(|token| token.token_type).collect();
+        assert_eq!(
+            token_types,
+            [
+                TokenType::LessLess,
+                TokenType::GreaterGreater,
+                TokenType::GreaterGreaterGreater,
+                TokenType::GreaterGreaterGreater,
+                TokenType::Greater,
+                TokenType::GreaterEqual,
+                TokenType::LessEqual,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dot_before_digit_starts_a_number() {
+        let types = |source: &str| -> Vec<TokenType> {
+            Scanner::new(source.to_string())
+                .scan_tokens()
+                .into_iter()
+                .map(|token| token.token_type)
+                .collect()
+        };
+
+        assert_eq!(types(".5"), [TokenType::Number(0.5), TokenType::Eof]);
+        assert_eq!(
+            types(".5.25"),
+            [
+                TokenType::Number(0.5),
+                TokenType::Number(0.25),
+                TokenType::Eof
+            ]
+        );
+        assert_eq!(
+            types("obj.field"),
+            [
+                TokenType::Identifier,
+                TokenType::Dot,
+                TokenType::Identifier,
+                TokenType::Eof
+            ]
+        );
+        assert_eq!(
+            types("a..b 1..5"),
+            [
+                TokenType::Identifier,
+                TokenType::DotDot,
+                TokenType::Identifier,
+                TokenType::Number(1.0),
+                TokenType::DotDot,
+                TokenType::Number(5.0),
+                TokenType::Eof
+            ]
+        );
+        assert_eq!(
+            types("3 . 5"),
+            [
+                TokenType::Number(3.0),
+                TokenType::Dot,
+                TokenType::Number(5.0),
+                TokenType::Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn test_octal_literals_and_leading_zeros() {
+        let scan = |source: &str| {
+            error::collect_diagnostics(|| Scanner::new(source.to_string()).scan_tokens())
+        };
+
+        let (tokens, diagnostics) = scan("0o17 0o0 0 0.5");
+        let token_types: Vec<TokenType> =
+            tokens.into_iter().map(|token| token.token_type).collect();
+        assert_eq!(
+            token_types,
+            [
+                TokenType::Number(15.0),
+                TokenType::Number(0.0),
+                TokenType::Number(0.0),
+                TokenType::Number(0.5),
+                TokenType::Eof,
+            ]
+        );
+        assert!(diagnostics.is_empty());
+
+        for (source, message) in [
+            (
+                "010",
+                "Leading zeros are not allowed; use 0o prefix for octal.",
+            ),
+            (
+                "00.5",
+                "Leading zeros are not allowed; use 0o prefix for octal.",
+            ),
+            ("0o18", "Invalid octal literal."),
+            ("0o", "Invalid octal literal."),
+        ] {
+            let (tokens, diagnostics) = scan(source);
+            assert_eq!(tokens.len(), 1, "{} should only scan to Eof", source);
+            assert_eq!(diagnostics.len(), 1, "{} should report an error", source);
+            assert_eq!(diagnostics[0].message, message);
+        }
+    }
+
+    #[test]
+    fn test_digit_separators_and_base_prefixes() {
+        let scan = |source: &str| {
+            error::collect_diagnostics(|| Scanner::new(source.to_string()).scan_tokens())
+        };
+
+        for (source, value) in [
+            ("1_000", 1000.0),
+            ("1_000.000_5", 1000.0005),
+            (".5_5", 0.55),
+            ("0xFF", 255.0),
+            ("0xff_ff", 65535.0),
+            ("0o1_7", 15.0),
+            ("0b1010", 10.0),
+            ("0b1010_1010", 170.0),
+            ("0b1_0_1", 5.0),
+        ] {
+            let (tokens, diagnostics) = scan(source);
+            assert!(diagnostics.is_empty(), "{} shouldn't report errors", source);
+            assert_eq!(
+                tokens[0].token_type,
+                TokenType::Number(value),
+                "{} should scan to {}",
+                source,
+                value
+            );
+            assert_eq!(tokens.len(), 2, "{} should be a single number", source);
+        }
+
+        for (source, message) in [
+            ("1__000", "Digit separator '_' must be between digits."),
+            ("1_", "Digit separator '_' must be between digits."),
+            ("1_.5", "Digit separator '_' must be between digits."),
+            ("1.5_", "Digit separator '_' must be between digits."),
+            ("0x_FF", "Digit separator '_' can't follow the base prefix."),
+            ("0o_17", "Digit separator '_' can't follow the base prefix."),
+            ("0b_1", "Digit separator '_' can't follow the base prefix."),
+            ("0xFF_", "Digit separator '_' must be between digits."),
+            ("0b10__10", "Digit separator '_' must be between digits."),
+            ("0x", "Invalid hexadecimal literal."),
+            ("0xFG", "Invalid hexadecimal literal."),
+            ("0b102", "Invalid binary literal."),
+            ("0b", "Invalid binary literal."),
+            ("0o1_8", "Invalid octal literal."),
+            (
+                "0_1",
+                "Leading zeros are not allowed; use 0o prefix for octal.",
+            ),
+        ] {
+            let (tokens, diagnostics) = scan(source);
+            assert_eq!(tokens.len(), 1, "{} should only scan to Eof", source);
+            assert_eq!(diagnostics.len(), 1, "{} should report an error", source);
+            assert_eq!(diagnostics[0].message, message, "for {}", source);
+        }
+    }
+
     #[test]
     fn test_with_lox_file() {
         let source =
@@ -290,4 +894,274 @@ mod tests {
 
         assert!(!tokens.is_empty());
     }
+
+    #[test]
+    fn test_number_after_non_ascii_characters() {
+        let source = String::from("\"ü\" 12");
+        let (tokens, diagnostics) =
+            error::collect_diagnostics(|| Scanner::new(source).scan_tokens());
+
+        assert!(diagnostics.is_empty());
+        assert_eq!(tokens[1].token_type, TokenType::Number(12.0));
+    }
+
+    #[test]
+    fn test_number_literal_overflowing_f64_is_reported() {
+        let source = format!("1{} + 2", "0".repeat(400));
+        let (tokens, diagnostics) =
+            error::collect_diagnostics(|| Scanner::new(source).scan_tokens());
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Invalid number literal.");
+        // Scanning continues after the bad literal
+        assert_eq!(tokens[0].token_type, TokenType::Plus);
+        assert_eq!(tokens[1].token_type, TokenType::Number(2.0));
+    }
+
+    #[test]
+    fn test_scanner_preserving_comments() {
+        let source =
+            std::fs::read_to_string("./test-scripts/comments.lox").expect("Failed to read file");
+
+        let tokens = Scanner::new(source).preserving_comments().scan_tokens();
+
+        let comments: Vec<(String, usize)> = tokens
+            .iter()
+            .filter_map(|token| match &token.token_type {
+                TokenType::Comment(text) => Some((text.clone(), token.line)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            comments,
+            [
+                (" Adds two numbers.".to_string(), 1),
+                (" inline".to_string(), 2),
+                (" A block comment\n   spanning lines ".to_string(), 3),
+            ]
+        );
+        assert_eq!(tokens[1].token_type, TokenType::Fun);
+        assert_eq!(tokens.last().map(|token| token.line), Some(6));
+    }
+
+    #[test]
+    fn test_scanner_discards_comments_by_default() {
+        let source = String::from("// line\n/* block */ nil");
+        let tokens = Scanner::new(source).scan_tokens();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token_type, TokenType::Nil);
+        assert_eq!(tokens[0].line, 2);
+    }
+
+    #[test]
+    fn test_raw_string_keeps_backslashes() {
+        let source = String::from(r#"r"\d+\.\d*" r"""#);
+        let (tokens, diagnostics) =
+            error::collect_diagnostics(|| Scanner::new(source).scan_tokens());
+
+        assert!(diagnostics.is_empty());
+        assert_eq!(tokens[0].token_type, TokenType::String(r"\d+\.\d*".into()));
+        assert_eq!(tokens[1].token_type, TokenType::String("".into()));
+    }
+
+    #[test]
+    fn test_hashed_raw_string_contains_quotes() {
+        let source = String::from(r###"r#"say "hi" \n"# r##"a "# b"## or"###);
+        let (tokens, diagnostics) =
+            error::collect_diagnostics(|| Scanner::new(source).scan_tokens());
+
+        assert!(diagnostics.is_empty());
+        assert_eq!(
+            tokens[0].token_type,
+            TokenType::String(r#"say "hi" \n"#.into())
+        );
+        assert_eq!(
+            tokens[1].token_type,
+            TokenType::String(r##"a "# b"##.into())
+        );
+        assert_eq!(tokens[2].token_type, TokenType::Or);
+    }
+
+    #[test]
+    fn test_unterminated_raw_string_is_reported() {
+        let source = String::from(r###"r#"missing "hash"###);
+        let (_, diagnostics) = error::collect_diagnostics(|| Scanner::new(source).scan_tokens());
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Unterminated raw string.");
+    }
+
+    #[test]
+    fn test_identifier_named_r_is_not_a_raw_string() {
+        let tokens = Scanner::new(String::from("r + rx")).scan_tokens();
+
+        assert_eq!(tokens[0].token_type, TokenType::Identifier);
+        assert_eq!(tokens[0].lexeme, "r");
+        assert_eq!(tokens[2].lexeme, "rx");
+    }
+
+    #[test]
+    fn test_token_columns() {
+        let tokens = Scanner::new(String::from("var ab = 1;\n  print ab;")).scan_tokens();
+
+        let columns: Vec<(usize, usize)> = tokens
+            .iter()
+            .map(|token| (token.line, token.column))
+            .collect();
+        assert_eq!(
+            columns,
+            [
+                (1, 1),
+                (1, 5),
+                (1, 8),
+                (1, 10),
+                (1, 11),
+                (2, 3),
+                (2, 9),
+                (2, 11),
+                (2, 12)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_leading_byte_order_mark_is_skipped() {
+        let source = String::from("\u{FEFF}var a;\nprint a;");
+        let (tokens, diagnostics) =
+            error::collect_diagnostics(|| Scanner::new(source).scan_tokens());
+
+        assert!(diagnostics.is_empty());
+        assert_eq!(tokens[0].token_type, TokenType::Var);
+        assert_eq!((tokens[0].line, tokens[0].column), (1, 1));
+        assert_eq!((tokens[1].line, tokens[1].column), (1, 5));
+        assert_eq!((tokens[3].line, tokens[3].column), (2, 1));
+
+        // Only at the start, elsewhere it's still an unexpected character
+        let (_, diagnostics) =
+            error::collect_diagnostics(|| Scanner::new(String::from("a\u{FEFF}")).scan_tokens());
+        assert_eq!(diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn test_tabs_advance_to_the_next_tab_stop() {
+        let source = String::from("\tprint a;\n  \tb;");
+
+        let tokens = Scanner::new(source.clone()).scan_tokens();
+        assert_eq!(tokens[0].column, 9);
+        assert_eq!(tokens[1].column, 15);
+        assert_eq!(tokens[3].column, 9);
+
+        let tokens = Scanner::new(source.clone()).with_tab_width(4).scan_tokens();
+        assert_eq!(tokens[0].column, 5);
+        assert_eq!(tokens[3].column, 5);
+
+        let tokens = Scanner::new(source).with_tab_width(1).scan_tokens();
+        assert_eq!(tokens[0].column, 2);
+        assert_eq!(tokens[3].column, 4);
+    }
+
+    #[test]
+    fn test_print_can_be_scanned_as_identifier() {
+        let tokens = Scanner::new(String::from("print")).scan_tokens();
+        assert_eq!(tokens[0].token_type, TokenType::Print);
+
+        let tokens = Scanner::new(String::from("print"))
+            .without_print_keyword()
+            .scan_tokens();
+        assert_eq!(tokens[0].token_type, TokenType::Identifier);
+    }
+
+    #[test]
+    fn test_streaming_scan_matches_scanning_the_whole_source() {
+        let mut source = String::from("\u{FEFF}");
+        for i in 0..20_000 {
+            source.push_str(&format!("var a{} = {} + \"s\"; // line {}\n", i, i, i));
+        }
+        // Tokens that span lines or look ahead
+        source.push_str("/* a\nblock */ \"multi\nline\" r#\"raw \" string\"# a >= 1");
+
+        let streamed = Scanner::from_reader(std::io::Cursor::new(source.clone())).scan_tokens();
+        let whole = Scanner::new(source).scan_tokens();
+
+        assert_eq!(streamed.len(), 20_000 * 7 + 6);
+        assert_eq!(streamed, whole);
+        assert_eq!(streamed[streamed.len() - 2].lexeme.as_str(), "1");
+        assert_eq!(streamed.last().unwrap().line, 20_003);
+    }
+
+    #[test]
+    fn test_over_long_literals_are_reported() {
+        let scan = |source: String| {
+            error::collect_diagnostics(|| {
+                Scanner::new(source)
+                    .with_max_string_length(8)
+                    .with_max_identifier_length(4)
+                    .scan_tokens()
+            })
+        };
+
+        let (tokens, diagnostics) = scan(format!(
+            "\"{}\" r#\"{}\"# abcd;",
+            "s".repeat(8),
+            "r".repeat(8)
+        ));
+        assert!(diagnostics.is_empty());
+        assert_eq!(tokens.len(), 5);
+
+        let (tokens, diagnostics) = scan(format!("print \"{}\";\nvar abcde;", "s".repeat(9)));
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "String literal is longer than the limit of 8 characters.",
+                "Identifier is longer than the limit of 4 characters."
+            ]
+        );
+        assert_eq!(diagnostics[1].line, 2);
+        // Scanning goes on after the literal
+        let token_types: Vec<&TokenType> = tokens.iter().map(|t| &t.token_type).collect();
+        assert_eq!(
+            token_types,
+            [
+                &TokenType::Print,
+                &TokenType::Semicolon,
+                &TokenType::Var,
+                &TokenType::Semicolon,
+                &TokenType::Eof
+            ]
+        );
+
+        let (_, diagnostics) = scan(format!("r\"{}\"", "r".repeat(9)));
+        assert_eq!(diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn test_over_long_literals_are_not_kept_while_they_are_skipped() {
+        let lines = "s".repeat(100) + "\n";
+        for (source, message) in [
+            (
+                format!("\"{}\"", lines.repeat(1000)),
+                "String literal is longer than the limit of 8 characters.",
+            ),
+            (
+                format!("r#\"{}\"#", lines.repeat(1000)),
+                "String literal is longer than the limit of 8 characters.",
+            ),
+            (
+                "i".repeat(100_000),
+                "Identifier is longer than the limit of 4 characters.",
+            ),
+        ] {
+            let mut scanner = Scanner::from_reader(std::io::Cursor::new(source))
+                .with_max_string_length(8)
+                .with_max_identifier_length(4);
+            let (_, diagnostics) = error::collect_diagnostics(|| scanner.scan_token());
+
+            assert_eq!(diagnostics.len(), 1);
+            assert_eq!(diagnostics[0].message, message);
+            assert!(scanner.is_at_end());
+            assert!(scanner.source_chars.len() < 1000);
+        }
+    }
 }
@@ -10,8 +10,15 @@ pub enum Stmt {
     Class {
         name: Token,
         superclass: Option<Expr>,
+        // Classes whose methods are copied into this one, see `class_declaration`
+        mixins: Vec<Expr>,
         methods: Vec<Stmt>, // Where statements must be functions
     },
+    // `enum Color { RED, GREEN }` defines `Color` with one distinct instance per variant
+    Enum {
+        name: Token,
+        variants: Vec<Token>,
+    },
     Expression {
         expr: Expr,
     },
@@ -28,16 +35,78 @@ pub enum Stmt {
     While {
         condition: Expr,
         body: Box<Stmt>,
+        // Evaluated after each iteration, only used by desugared for loops
+        increment: Option<Expr>,
+        // Give every iteration its own copy of the enclosing scope (the loop variable of a
+        // desugared for loop), so closures created in the body capture that iteration's value
+        scope_per_iteration: bool,
+    },
+    // `for (var name in iterable)` runs the body once per element of a list or string
+    ForIn {
+        name: Token,
+        keyword: Token,
+        iterable: Expr,
+        body: Box<Stmt>,
     },
     Print {
         expr: Expr,
     },
+    // Same as print, but writes to the error output
+    Eprint {
+        expr: Expr,
+    },
+    // A statement rather than a native function, so the message is only evaluated on failure
+    Assert {
+        keyword: Token,
+        condition: Expr,
+        message: Option<Expr>,
+    },
     Return {
         keyword: Token,
         value: Option<Expr>,
     },
+    // Exits the innermost loop or switch
+    Break {
+        keyword: Token,
+    },
+    // Skips to the next iteration of the innermost loop, switches don't stop it
+    Continue {
+        keyword: Token,
+    },
+    // Runs the first case equal to the value or else the default, cases don't fall through
+    Switch {
+        keyword: Token,
+        value: Expr,
+        cases: Vec<(Expr, Vec<Stmt>)>,
+        default: Option<Vec<Stmt>>,
+    },
+    // Ends the enclosing try blocks up to the first one catching the value
+    Throw {
+        keyword: Token,
+        value: Expr,
+    },
+    // The finally block runs however the body or the catch block are left, including by return,
+    // break and continue. Only thrown values are caught, runtime errors still end the program.
+    Try {
+        body: Vec<Stmt>,
+        // The name the thrown value is bound to and the block handling it
+        catch: Option<(Token, Vec<Stmt>)>,
+        finally: Option<Vec<Stmt>>,
+    },
     Var {
         name: Token,
         initializer: Option<Expr>,
     },
+    // A statement that only parsed after inserting the missing token, see
+    // `Parser::with_recovery`. Tools can inspect it, but it can't run.
+    Incomplete {
+        missing: Token,
+        statement: Box<Stmt>,
+    },
+    // `var [a, b] = list;` binds each name to the element at its position
+    VarDestructure {
+        bracket: Token,
+        names: Vec<Token>,
+        initializer: Expr,
+    },
 }
@@ -0,0 +1,116 @@
+use std::{cell::RefCell, collections::HashMap, fmt};
+
+/// An interned string. Comparing, hashing and copying a symbol only touches a `u32`,
+/// which makes it a cheap key for environments, scopes, fields and methods.
+///
+/// Interned strings live for the rest of the program (they are leaked into the interner),
+/// so only names from the source (see `Lexeme`) and a handful of builtin names should be interned.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    strings: Vec<&'static str>,
+}
+
+// Names the interpreter looks up on hot paths (binding methods, calling initializers, comparing
+// instances). They are interned first, so their symbols are known at compile time.
+const PREDEFINED: [&str; 4] = ["this", "super", "init", "equals"];
+
+impl Interner {
+    fn new() -> Interner {
+        Interner {
+            symbols: PREDEFINED
+                .iter()
+                .enumerate()
+                .map(|(i, string)| (*string, Symbol(i as u32)))
+                .collect(),
+            strings: PREDEFINED.to_vec(),
+        }
+    }
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::new());
+}
+
+impl Symbol {
+    pub const THIS: Symbol = Symbol(0);
+    pub const SUPER: Symbol = Symbol(1);
+    pub const INIT: Symbol = Symbol(2);
+    pub const EQUALS: Symbol = Symbol(3);
+
+    pub fn intern(string: &str) -> Symbol {
+        INTERNER.with(|interner| {
+            let mut interner = interner.borrow_mut();
+
+            if let Some(symbol) = interner.symbols.get(string) {
+                return *symbol;
+            }
+
+            let string: &'static str = Box::leak(string.to_owned().into_boxed_str());
+            let symbol = Symbol(interner.strings.len() as u32);
+            interner.strings.push(string);
+            interner.symbols.insert(string, symbol);
+            symbol
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.with(|interner| interner.borrow().strings[self.0 as usize])
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(string: &str) -> Symbol {
+        Symbol::intern(string)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Symbol;
+
+    #[test]
+    fn test_intern_returns_same_symbol_for_equal_strings() {
+        let first = Symbol::intern("counter");
+        let second = Symbol::intern(&String::from("counter"));
+
+        assert_eq!(first, second);
+        assert_ne!(first, Symbol::intern("count"));
+    }
+
+    #[test]
+    fn test_symbol_resolves_to_original_string() {
+        let symbol = Symbol::intern("increment");
+
+        assert_eq!(symbol.as_str(), "increment");
+        assert_eq!(symbol.to_string(), "increment");
+        assert!(symbol == "increment");
+    }
+
+    #[test]
+    fn test_predefined_symbols() {
+        assert_eq!(Symbol::intern("this"), Symbol::THIS);
+        assert_eq!(Symbol::intern("super"), Symbol::SUPER);
+        assert_eq!(Symbol::intern("init"), Symbol::INIT);
+    }
+}
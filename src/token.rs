@@ -1,4 +1,11 @@
-use crate::token_type::TokenType;
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use crate::{symbol::Symbol, token_type::TokenType};
 
 #[derive(Debug, PartialEq)]
 pub enum Literal {
@@ -8,19 +15,193 @@ pub enum Literal {
     Boolean(bool),
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: TokenType,
-    pub lexeme: String,
+    pub lexeme: Lexeme,
     pub line: usize,
+    // Where the token starts on its line, counting from 1, or 0 if it wasn't scanned from source
+    pub column: usize,
+}
+
+// The text of a token. Names, keywords and operators are interned, as they repeat and are looked
+// up all the time. Literals and comments are mostly unique, and interned strings are never freed.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum Lexeme {
+    Symbol(Symbol),
+    Text(Arc<str>),
+}
+
+impl Lexeme {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Lexeme::Symbol(symbol) => symbol.as_str(),
+            Lexeme::Text(text) => text,
+        }
+    }
+
+    // The lexeme as a name, e.g. of a variable. Names are always interned already.
+    pub fn symbol(&self) -> Symbol {
+        match self {
+            Lexeme::Symbol(symbol) => *symbol,
+            Lexeme::Text(text) => Symbol::intern(text),
+        }
+    }
+}
+
+impl From<Symbol> for Lexeme {
+    fn from(symbol: Symbol) -> Lexeme {
+        Lexeme::Symbol(symbol)
+    }
+}
+
+impl From<&str> for Lexeme {
+    fn from(string: &str) -> Lexeme {
+        Lexeme::Symbol(Symbol::intern(string))
+    }
+}
+
+impl fmt::Display for Lexeme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq<&str> for Lexeme {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<Symbol> for Lexeme {
+    fn eq(&self, other: &Symbol) -> bool {
+        *self == Lexeme::Symbol(*other)
+    }
+}
+
+// Where a token is in the source, columns count chars
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, lexeme: String, line: usize) -> Self {
+    pub fn new(token_type: TokenType, lexeme: impl Into<Lexeme>, line: usize) -> Self {
         Token {
             token_type,
-            lexeme,
+            lexeme: lexeme.into(),
             line,
+            column: 0,
+        }
+    }
+
+    pub fn at_column(mut self, column: usize) -> Self {
+        self.column = column;
+        self
+    }
+
+    pub fn span(&self) -> Span {
+        Span {
+            line: self.line,
+            column: self.column,
+            length: self.lexeme.as_str().chars().count(),
         }
     }
 }
+
+// Tokens are equal if they compare equal, see `Ord`
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Token {}
+
+// Equal tokens are at the same place with the same text, which is all that's hashed
+impl Hash for Token {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.line, self.column, &self.lexeme).hash(state);
+    }
+}
+
+// By where the tokens are in the source, e.g. to sort diagnostics by location. Tokens at the
+// same place are ordered by their text and then their type, so only equal tokens compare equal.
+impl Ord for Token {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.line, self.column, self.lexeme.as_str())
+            .cmp(&(other.line, other.column, other.lexeme.as_str()))
+            .then_with(|| compare_types(&self.token_type, &other.token_type))
+    }
+}
+
+// Number literals are compared by their bits, so that every token is equal to itself, even one
+// that a tool built with NaN
+fn compare_types(left: &TokenType, right: &TokenType) -> Ordering {
+    match (left, right) {
+        (TokenType::Number(left), TokenType::Number(right)) => left.total_cmp(right),
+        _ => left
+            .partial_cmp(right)
+            .expect("Only number literals can be unordered"),
+    }
+}
+
+impl PartialOrd for Token {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::Token;
+    use crate::{scanner::Scanner, token_type::TokenType};
+
+    #[test]
+    fn test_tokens_sort_by_position() {
+        let scanned = Scanner::new("var b =\n  a + c;".to_string()).scan_tokens();
+
+        let mut tokens = scanned.clone();
+        tokens.reverse();
+        tokens.sort();
+        assert_eq!(tokens, scanned);
+
+        // Desugared tokens without a column come first on their line
+        let mut tokens = [
+            scanned[3].clone(),
+            Token::new(TokenType::Identifier, "z", 2),
+            Token::new(TokenType::Identifier, "a", 2),
+        ];
+        tokens.sort();
+        let lexemes: Vec<&str> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+        assert_eq!(lexemes, ["a", "z", "a"]);
+    }
+
+    #[test]
+    fn test_number_tokens_are_equal_to_themselves() {
+        let nan = Token::new(TokenType::Number(f64::NAN), "nan", 1);
+        assert_eq!(nan, nan.clone());
+        assert_eq!(nan.cmp(&nan.clone()), std::cmp::Ordering::Equal);
+
+        // Numbers that are equal but have different bits make different tokens
+        let zero = Token::new(TokenType::Number(0.0), "0", 1);
+        let negative_zero = Token::new(TokenType::Number(-0.0), "0", 1);
+        assert_ne!(zero, negative_zero);
+        assert_ne!(zero.cmp(&negative_zero), std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn test_tokens_can_be_map_keys() {
+        let scanned = Scanner::new("a a".to_string()).scan_tokens();
+        let keys: HashSet<Token> = scanned.iter().cloned().collect();
+
+        // The same name in two places are two tokens
+        assert_eq!(keys.len(), 3);
+        assert!(keys.contains(&scanned[1]));
+        assert!(!keys.contains(&Token::new(TokenType::Identifier, "a", 1)));
+    }
+}
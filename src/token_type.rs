@@ -1,10 +1,12 @@
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, PartialOrd, Clone)]
 pub enum TokenType {
     // Single-character tokens.
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
@@ -24,6 +26,18 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    Arrow,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    DotDot,
+    QuestionColon,
+    PipePipeEqual,
+    AmpAmpEqual,
+    LessLess,
+    GreaterGreater,
+    GreaterGreaterGreater,
 
     // Literals.
     Identifier,
@@ -32,21 +46,49 @@ pub enum TokenType {
 
     // Keywords.
     And,
+    Assert,
+    Break,
+    Case,
+    Catch,
     Class,
+    Continue,
+    Default,
+    Finally,
+    Delete,
     Else,
+    Enum,
+    Eprint,
     False,
     Fun,
     For,
     If,
+    In,
+    Is,
+    Loop,
     Nil,
     Or,
     Print,
     Return,
     Super,
+    Switch,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
+    With,
+
+    // Only produced by scanners preserving comments, holds the text between the delimiters
+    Comment(String),
 
     Eof,
 }
+
+impl TokenType {
+    // Whether the operator of a compound assignment is `||=` or `&&=`, which only assign if the
+    // current value is falsey or truthy respectively
+    pub fn is_logical(&self) -> bool {
+        matches!(self, TokenType::PipePipeEqual | TokenType::AmpAmpEqual)
+    }
+}
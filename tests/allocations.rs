@@ -0,0 +1,104 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::{
+        atomic::{AtomicIsize, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use rlox::interpreter::Interpreter;
+
+// Counts every allocation of this test binary, so the tests here take turns, see `measure`
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static LIVE_BYTES: AtomicIsize = AtomicIsize::new(0);
+static MEASURING: Mutex<()> = Mutex::new(());
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        LIVE_BYTES.fetch_add(layout.size() as isize, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_BYTES.fetch_sub(layout.size() as isize, Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// The allocations made while running `f`, and how many bytes of them are still allocated after
+fn measure(f: impl FnOnce()) -> (usize, isize) {
+    let allocations = ALLOCATIONS.load(Ordering::SeqCst);
+    let live_bytes = LIVE_BYTES.load(Ordering::SeqCst);
+    f();
+    (
+        ALLOCATIONS.load(Ordering::SeqCst) - allocations,
+        LIVE_BYTES.load(Ordering::SeqCst) - live_bytes,
+    )
+}
+
+// Runs a loop whose body looks up `lookups` variables on every iteration
+fn run_lookups(lookups: usize) -> usize {
+    let body = "a; b; counter.count; ".repeat(lookups);
+    let source = format!(
+        "class Counter {{}} var counter = Counter(); counter.count = 0; var a = 1; var b = 2;
+         for (var i = 0; i < 1000; i = i + 1) {{ {} }}",
+        body
+    );
+
+    let (statements, _) = rlox::parse(&source);
+    let mut statements = statements.expect("Should parse");
+    assert!(rlox::resolve(&mut statements).is_empty());
+
+    let mut interpreter = Interpreter::default();
+    let (allocations, _) = measure(|| {
+        rlox::evaluate(&mut interpreter, &statements).expect("Should run");
+    });
+    allocations
+}
+
+#[test]
+fn bench_looking_up_names_does_not_allocate() {
+    let _guard = MEASURING.lock().unwrap();
+
+    let baseline = run_lookups(1);
+    let with_lookups = run_lookups(10);
+    println!(
+        "{} allocations with 3000 lookups of variables and fields, {} with 30000",
+        baseline, with_lookups
+    );
+
+    assert!(
+        with_lookups == baseline,
+        "Expected lookups not to allocate, got {} more allocations",
+        with_lookups - baseline
+    );
+}
+
+#[test]
+fn bench_literals_are_freed_with_their_tokens() {
+    let _guard = MEASURING.lock().unwrap();
+
+    // Sets up the interner and the diagnostics, which live as long as the thread
+    rlox::tokenize("print \"warm up\";");
+
+    let literals: String = (0..1000)
+        .map(|i| format!("\"string {i}\" {i}.5 // comment {i}\n"))
+        .collect();
+    let (_, live_bytes) = measure(|| {
+        rlox::tokenize(&literals);
+    });
+    assert_eq!(live_bytes, 0, "Expected literals to be freed");
+
+    // Unlike names, which are interned for good
+    let names: String = (0..1000).map(|i| format!("name{i} ")).collect();
+    let (_, live_bytes) = measure(|| {
+        rlox::tokenize(&names);
+    });
+    assert!(live_bytes > 0);
+}
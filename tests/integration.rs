@@ -9,13 +9,15 @@ fn run_lox_test(test_path: &str) {
     let test_file = PathBuf::from(test_path);
     println!("Running test: {}", test_file.display());
 
-    let (expected_output, expected_errors) =
+    let (expected_output, expected_errors, env_vars, args) =
         parse_expectations(&test_file).expect("Failed to parse test expectations");
 
     let interpreter_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/debug/rlox");
 
     let output = Command::new(&interpreter_path)
+        .args(args)
         .arg(&test_file)
+        .envs(env_vars)
         .output()
         .expect("Failed to execute interpreter");
 
@@ -41,14 +43,85 @@ fn run_lox_test(test_path: &str) {
     }
 }
 
-fn parse_expectations(test_file: &Path) -> Result<(Vec<String>, Vec<String>), std::io::Error> {
+fn check(script: &str) -> std::process::Output {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    Command::new(manifest_dir.join("target/debug/rlox"))
+        .arg("--check")
+        .arg(manifest_dir.join("test-scripts/check").join(script))
+        .output()
+        .expect("Failed to execute interpreter")
+}
+
+#[test]
+fn check_reports_diagnostics_without_running() {
+    let output = check("resolver_error.lox");
+
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 4] Error at 'a': Already a variable with this name in this scope.\n  var a = 2;\n      ^\n"
+    );
+}
+
+#[test]
+fn check_passes_clean_scripts_without_running() {
+    let output = check("clean.lox");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn run_underlines_diagnostics_like_check() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let output = Command::new(manifest_dir.join("target/debug/rlox"))
+        .arg(manifest_dir.join("test-scripts/check/resolver_error.lox"))
+        .output()
+        .expect("Failed to execute interpreter");
+
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 4] Error at 'a': Already a variable with this name in this scope.\n  var a = 2;\n      ^\n"
+    );
+}
+
+#[test]
+fn sleep_longer_than_a_duration_is_a_runtime_error() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let output = Command::new(manifest_dir.join("target/debug/rlox"))
+        .arg(manifest_dir.join("test-scripts/integration/function/sleep_too_long.lox"))
+        .env("RLOX_INSTANT_SLEEP", "1")
+        .output()
+        .expect("Failed to execute interpreter");
+
+    // Not a panic, which would exit with 101
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 3]: sleep() can't wait that long.\n"
+    );
+}
+
+type Expectations = (Vec<String>, Vec<String>, Vec<(String, String)>, Vec<String>);
+
+fn parse_expectations(test_file: &Path) -> Result<Expectations, std::io::Error> {
     let content = fs::read_to_string(test_file)?;
 
     let mut expected_output = Vec::new();
     let mut expected_errors = Vec::new();
+    let mut env_vars = Vec::new();
+    let mut args = Vec::new();
 
     let expect_regex = Regex::new(r"// expect:\s*(.+)").unwrap();
-    let error_regex = Regex::new(r"// (error|Error).*:\s*(.+)").unwrap();
+    let error_regex = Regex::new(r"// (error|Error|Warning).*:\s*(.+)").unwrap();
+    // Environment variables to run the interpreter with, e.g. `// env: RLOX_FIXED_CLOCK=1`
+    let env_regex = Regex::new(r"// env:\s*(\w+)=(.*)").unwrap();
+    // Flags to run the interpreter with, e.g. `// args: --print-last`
+    let args_regex = Regex::new(r"// args:\s*(.+)").unwrap();
 
     for line in content.lines() {
         if let Some(captures) = expect_regex.captures(line) {
@@ -62,7 +135,15 @@ fn parse_expectations(test_file: &Path) -> Result<(Vec<String>, Vec<String>), st
                 expected_errors.push(expected.as_str().trim().to_string());
             }
         }
+
+        if let Some(captures) = env_regex.captures(line) {
+            env_vars.push((captures[1].to_string(), captures[2].trim().to_string()));
+        }
+
+        if let Some(captures) = args_regex.captures(line) {
+            args.extend(captures[1].split_whitespace().map(str::to_string));
+        }
     }
 
-    Ok((expected_output, expected_errors))
+    Ok((expected_output, expected_errors, env_vars, args))
 }